@@ -0,0 +1,187 @@
+//! SHA256 verification of downloaded release archives.
+//!
+//! Every `shebe-{version}-{os}-{arch}.tar.gz` asset is
+//! published next to a `.sha256` sidecar in
+//! `sha256sum` format (`<hex>  <filename>`); release
+//! manifests publish the digest inline instead. The archive
+//! is only extracted once its digest matches.
+
+use std::fs::File;
+use std::io::{self, Read};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::ShebeError;
+
+/// Extract the expected digest from a `.sha256` sidecar.
+///
+/// Accepts both the bare `<hex>` form and the
+/// `sha256sum` form. When a filename is present it must
+/// match `asset_name` so a sidecar for the wrong archive
+/// is rejected.
+pub fn parse_sidecar(
+    contents: &str,
+    asset_name: &str,
+) -> Result<String, String> {
+    let mut fields = contents.split_whitespace();
+    let digest = fields.next().ok_or_else(|| {
+        format!("checksum file for '{}' is empty", asset_name)
+    })?;
+
+    if digest.len() != 64
+        || !digest.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return Err(format!(
+            "checksum file for '{}' does not contain a \
+             SHA256 digest",
+            asset_name,
+        ));
+    }
+
+    if let Some(name) = fields.next() {
+        // sha256sum marks binary mode with a leading '*'
+        let name = name.trim_start_matches('*');
+        if name != asset_name {
+            return Err(format!(
+                "checksum file lists '{}', expected '{}'",
+                name, asset_name,
+            ));
+        }
+    }
+
+    Ok(digest.to_ascii_lowercase())
+}
+
+/// Compute the lowercase hex SHA256 digest of a file.
+pub fn sha256_file(path: &str) -> io::Result<String> {
+    let mut file = File::open(path)?;
+
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Verify `archive_path` against the digest stored in the
+/// sidecar at `sidecar_path`.
+pub fn verify(
+    archive_path: &str,
+    sidecar_path: &str,
+    asset_name: &str,
+) -> Result<(), ShebeError> {
+    let invalid = |error: String| ShebeError::InvalidChecksum {
+        asset: asset_name.to_string(),
+        error,
+    };
+    let contents = std::fs::read_to_string(sidecar_path)
+        .map_err(|e| invalid(e.to_string()))?;
+    let expected =
+        parse_sidecar(&contents, asset_name).map_err(invalid)?;
+    verify_digest(archive_path, asset_name, &expected)
+}
+
+/// Verify `archive_path` against an expected hex digest.
+pub fn verify_digest(
+    archive_path: &str,
+    asset_name: &str,
+    expected: &str,
+) -> Result<(), ShebeError> {
+    let expected = expected.to_ascii_lowercase();
+    let actual = sha256_file(archive_path).map_err(|e| {
+        ShebeError::Permission {
+            path: archive_path.to_string(),
+            error: e.to_string(),
+        }
+    })?;
+
+    if actual != expected {
+        return Err(ShebeError::ChecksumMismatch {
+            asset: asset_name.to_string(),
+            expected,
+            actual,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASSET: &str = "shebe-v0.5.7-linux-x86_64.tar.gz";
+    /// SHA256 of `b"shebe"`.
+    const DIGEST: &str =
+        "89745bf39b91f697fc63ea64da218427a2ac3fb1b63ea5ab5c42603405d5893d";
+
+    #[test]
+    fn parses_bare_and_sha256sum_forms() {
+        let upper = DIGEST.to_ascii_uppercase();
+        assert_eq!(parse_sidecar(&upper, ASSET).unwrap(), DIGEST);
+        assert_eq!(
+            parse_sidecar(&format!("{}  {}\n", DIGEST, ASSET), ASSET)
+                .unwrap(),
+            DIGEST,
+        );
+        assert_eq!(
+            parse_sidecar(&format!("{} *{}", DIGEST, ASSET), ASSET)
+                .unwrap(),
+            DIGEST,
+        );
+    }
+
+    #[test]
+    fn rejects_sidecar_for_another_file() {
+        let contents = format!("{}  shebe-v0.5.6.tar.gz", DIGEST);
+        let err = parse_sidecar(&contents, ASSET).unwrap_err();
+        assert!(err.contains("lists 'shebe-v0.5.6.tar.gz'"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_malformed_digests() {
+        for contents in [&DIGEST[..63], &DIGEST.replace('a', "g")] {
+            let err = parse_sidecar(contents, ASSET).unwrap_err();
+            assert!(err.contains("SHA256 digest"), "{}", err);
+        }
+        for contents in ["", " \n"] {
+            let err = parse_sidecar(contents, ASSET).unwrap_err();
+            assert!(err.contains("is empty"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn verifies_archive_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(ASSET);
+        std::fs::write(&path, b"shebe").unwrap();
+        let path = path.to_str().unwrap();
+
+        let actual = sha256_file(path).unwrap();
+        assert_eq!(actual, DIGEST);
+        verify_digest(path, ASSET, &actual.to_ascii_uppercase())
+            .unwrap();
+
+        let sidecar = dir.path().join("sidecar");
+        std::fs::write(&sidecar, format!("{}  {}", actual, ASSET))
+            .unwrap();
+        verify(path, sidecar.to_str().unwrap(), ASSET).unwrap();
+
+        match verify_digest(path, ASSET, &"0".repeat(64)) {
+            Err(ShebeError::ChecksumMismatch { actual: a, .. }) => {
+                assert_eq!(a, actual);
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+    }
+}
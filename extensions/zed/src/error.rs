@@ -0,0 +1,151 @@
+//! Errors raised while resolving and installing `shebe-mcp`.
+//!
+//! Zed shows the `String` returned from
+//! `context_server_command` to the user verbatim, so every
+//! variant renders as a short explanation, what to try next
+//! and a link to the README's troubleshooting section.
+
+use std::fmt;
+
+use crate::platform::PlatformError;
+
+/// Troubleshooting section of the extension README.
+pub const TROUBLESHOOTING_URL: &str =
+    "https://github.com/rhobimd-oss/shebe-releases/tree/main/extensions/zed#troubleshooting";
+
+/// A failure to provide a runnable `shebe-mcp`.
+#[derive(Debug)]
+pub enum ShebeError {
+    /// No shebe build exists for this OS or architecture.
+    UnsupportedPlatform(PlatformError),
+    /// The release lacks the archive or checksum for this
+    /// platform.
+    MissingAsset(PlatformError),
+    /// The release could not be looked up and nothing usable
+    /// is installed from an earlier session.
+    ReleaseLookup { error: String },
+    /// A release asset could not be downloaded.
+    Download { url: String, error: String },
+    /// The archive's digest differs from its sidecar.
+    ChecksumMismatch {
+        asset: String,
+        expected: String,
+        actual: String,
+    },
+    /// The `.sha256` sidecar is unreadable or malformed.
+    InvalidChecksum { asset: String, error: String },
+    /// The verified archive could not be unpacked.
+    Extraction { archive: String, error: String },
+    /// The installed binary failed `shebe-mcp --version`
+    /// twice in a row.
+    HealthCheck { binary: String, error: String },
+    /// `version: "previous"` is set but there is nothing to
+    /// roll back to.
+    NoPreviousInstall,
+    /// A file in the work dir could not be read, written or
+    /// made executable.
+    Permission { path: String, error: String },
+}
+
+impl From<PlatformError> for ShebeError {
+    fn from(error: PlatformError) -> Self {
+        match error {
+            PlatformError::UnsupportedOs(_)
+            | PlatformError::UnsupportedArch(_)
+            | PlatformError::LinuxArmUnavailable { .. } => {
+                Self::UnsupportedPlatform(error)
+            }
+            PlatformError::MissingAsset { .. }
+            | PlatformError::MissingChecksum { .. } => {
+                Self::MissingAsset(error)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ShebeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedPlatform(e) => write!(
+                f,
+                "{}. Build shebe-mcp from source and set \
+                 `binary_path` to use it anyway.",
+                e,
+            )?,
+            Self::MissingAsset(e) => write!(
+                f,
+                "{}. The release may still be uploading; retry \
+                 later or pin an earlier `version`.",
+                e,
+            )?,
+            Self::ReleaseLookup { error } => write!(
+                f,
+                "failed to look up shebe-mcp releases: {}; no \
+                 previously installed shebe-mcp to fall back to. \
+                 Check network access to github.com or the \
+                 configured `release_source`.",
+                error,
+            )?,
+            Self::Download { url, error } => write!(
+                f,
+                "failed to download {}: {}. Check network access \
+                 to the release host and restart the context \
+                 server.",
+                url, error,
+            )?,
+            Self::ChecksumMismatch {
+                asset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch for '{}': expected {}, got {}. \
+                 The download was discarded; restart the context \
+                 server to retry.",
+                asset, expected, actual,
+            )?,
+            Self::InvalidChecksum { asset, error } => write!(
+                f,
+                "cannot verify '{}': {}. Refusing to install an \
+                 unverified binary.",
+                asset, error,
+            )?,
+            Self::Extraction { archive, error } => write!(
+                f,
+                "failed to extract '{}': {}. The archive was \
+                 discarded; restart the context server to retry.",
+                archive, error,
+            )?,
+            Self::HealthCheck { binary, error } => write!(
+                f,
+                "downloaded {} failed its health check: {}. The \
+                 install was moved to the extension's quarantine \
+                 directory; pin an earlier `version` or set \
+                 `binary_path` to a working shebe-mcp.",
+                binary, error,
+            )?,
+            Self::NoPreviousInstall => write!(
+                f,
+                "`version` is \"previous\" but no earlier \
+                 shebe-mcp install is recorded. Set `version` to \
+                 \"latest\" or pin a release instead.",
+            )?,
+            Self::Permission { path, error } => write!(
+                f,
+                "cannot access '{}': {}. Check that Zed's \
+                 extension directory is writable and not mounted \
+                 noexec.",
+                path, error,
+            )?,
+        }
+        write!(f, " See {}", TROUBLESHOOTING_URL)
+    }
+}
+
+impl std::error::Error for ShebeError {}
+
+impl From<ShebeError> for String {
+    fn from(error: ShebeError) -> Self {
+        error.to_string()
+    }
+}
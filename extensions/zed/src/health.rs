@@ -0,0 +1,160 @@
+//! Post-install health check of a downloaded `shebe-mcp`.
+//!
+//! A corrupt or wrong-arch binary would otherwise only show
+//! up as an opaque context server crash. Running
+//! `shebe-mcp --version` right after extraction catches it
+//! while the extension can still do something about it:
+//! the install is moved aside into [`QUARANTINE_DIR`] and
+//! downloaded once more.
+
+use std::fs;
+
+use semver::Version;
+use zed_extension_api::process::Command;
+
+/// Work dir subdirectory holding installs that failed
+/// their health check, kept for inspection.
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// Runs `"$0" --version` for a binary named `shebe-mcp`
+/// and refuses anything else. The `process:exec` capability
+/// in `extension.toml` grants exactly this script, so the
+/// extension cannot run arbitrary binaries with
+/// `--version`.
+pub const VERSION_SCRIPT: &str = "case \"$0\" in */shebe-mcp) \
+                                  exec \"$0\" --version ;; esac\n\
+                                  echo \"not a shebe-mcp binary\" >&2\n\
+                                  exit 126";
+
+/// Run `<binary> --version` and parse the reported version.
+///
+/// Accepts output such as `shebe-mcp 0.5.7` or
+/// `shebe-mcp v0.5.7`; the last whitespace-separated field
+/// is taken as the version. Only binaries named `shebe-mcp`
+/// are run, see [`VERSION_SCRIPT`].
+pub fn version(binary: &str) -> Result<Version, String> {
+    let output = Command::new("/bin/sh")
+        .args(["-c", VERSION_SCRIPT, binary])
+        .output()
+        .map_err(|e| format!("failed to run: {}", e))?;
+    if output.status != Some(0) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match output.status {
+            Some(code) => format!(
+                "--version exited with status {}: {}",
+                code,
+                stderr.trim(),
+            ),
+            None => format!(
+                "--version was killed by a signal: {}",
+                stderr.trim(),
+            ),
+        });
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let field = stdout
+        .split_whitespace()
+        .last()
+        .ok_or("--version printed nothing")?;
+    Version::parse(field.trim_start_matches('v')).map_err(|e| {
+        format!("unexpected --version output '{}': {}", field, e)
+    })
+}
+
+/// Check that `binary` runs and reports the version of the
+/// release tagged `tag`.
+///
+/// Only major.minor.patch are compared: forks and mirrors
+/// often tag builds such as `v0.6.0-acme.1` while the binary
+/// reports plain `0.6.0`, or the other way round.
+pub fn check(binary: &str, tag: &str) -> Result<(), String> {
+    let expected = Version::parse(tag.trim_start_matches('v'))
+        .map_err(|e| {
+            format!("release tag '{}' is not a version: {}", tag, e)
+        })?;
+    let reported = version(binary)?;
+    if !same_release(&reported, &expected) {
+        return Err(format!(
+            "reports version {} but release is {}",
+            reported, tag,
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `a` and `b` share major.minor.patch, ignoring
+/// pre-release and build metadata.
+fn same_release(a: &Version, b: &Version) -> bool {
+    (a.major, a.minor, a.patch) == (b.major, b.minor, b.patch)
+}
+
+/// Move the install in `dir` into [`QUARANTINE_DIR`],
+/// replacing any earlier quarantined copy. Falls back to
+/// deleting it so a broken install is never picked up
+/// again.
+pub fn quarantine(dir: &str) {
+    let target = format!("{}/{}", QUARANTINE_DIR, dir);
+    fs::remove_dir_all(&target).ok();
+    let moved = fs::create_dir_all(QUARANTINE_DIR)
+        .and_then(|()| fs::rename(dir, &target));
+    if let Err(e) = moved {
+        eprintln!(
+            "shebe: failed to quarantine {} ({}); removing it",
+            dir, e,
+        );
+        fs::remove_dir_all(dir).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_script_is_granted() {
+        let script = serde_json::to_string(VERSION_SCRIPT).unwrap();
+        let manifest = include_str!("../extension.toml");
+        assert!(
+            manifest.contains(&format!("args = [\"-c\", {}, \"*\"]", script)),
+            "extension.toml must grant /bin/sh -c {}",
+            script,
+        );
+    }
+
+    #[test]
+    fn version_script_only_runs_shebe_mcp() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["shebe-mcp", "other"] {
+            let path = dir.path().join(name);
+            fs::write(&path, "#!/bin/sh\necho \"shebe-mcp v0.5.7\"\n")
+                .unwrap();
+            std::process::Command::new("chmod")
+                .arg("+x")
+                .arg(&path)
+                .status()
+                .unwrap();
+        }
+        let run = |name: &str| {
+            std::process::Command::new("/bin/sh")
+                .args(["-c", VERSION_SCRIPT])
+                .arg(dir.path().join(name))
+                .output()
+                .unwrap()
+        };
+        let ok = run("shebe-mcp");
+        assert_eq!(String::from_utf8_lossy(&ok.stdout), "shebe-mcp v0.5.7\n");
+        let refused = run("other");
+        assert_eq!(refused.status.code(), Some(126));
+        assert!(refused.stdout.is_empty());
+    }
+    #[test]
+    fn compares_release_numbers_only() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        assert!(same_release(&v("0.6.0"), &v("0.6.0")));
+        assert!(same_release(&v("0.6.0"), &v("0.6.0-acme.1")));
+        assert!(same_release(&v("0.6.0+acme.1"), &v("0.6.0")));
+        assert!(!same_release(&v("0.6.1"), &v("0.6.0-acme.1")));
+        assert!(!same_release(&v("0.5.7"), &v("0.6.0")));
+    }
+}
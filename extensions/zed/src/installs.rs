@@ -0,0 +1,124 @@
+//! Inspection of `shebe-*` install dirs in the work dir.
+//!
+//! Each successful install leaves a
+//! `{channel prefix}{version}/shebe-mcp` behind. These are
+//! what the extension falls back to when GitHub cannot be
+//! reached, and what gets pruned once a newer release is
+//! installed.
+
+use std::fs;
+use std::path::Path;
+
+use semver::Version;
+
+use crate::release::Channel;
+
+/// A previously extracted `shebe-mcp`.
+#[derive(Debug, Clone)]
+pub struct Install {
+    pub version: Version,
+    /// Directory name relative to the work dir.
+    pub dir: String,
+}
+
+impl Install {
+    /// Binary path relative to the work dir.
+    pub fn binary_path(&self) -> String {
+        format!("{}/shebe-mcp", self.dir)
+    }
+}
+
+/// All installs from `channel` that still contain a
+/// `shebe-mcp` binary, newest first.
+pub fn list(channel: Channel) -> Vec<Install> {
+    list_in(Path::new("."), channel)
+}
+
+/// [`list`] for the work dir at `root`.
+fn list_in(root: &Path, channel: Channel) -> Vec<Install> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+
+    let mut installs: Vec<Install> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let dir = entry.file_name().into_string().ok()?;
+            let tag = dir.strip_prefix(channel.dir_prefix())?;
+            let version =
+                Version::parse(tag.trim_start_matches('v'))
+                    .ok()?;
+            let install = Install { version, dir };
+            let binary =
+                fs::metadata(root.join(install.binary_path()))
+                    .ok()?;
+            (binary.is_file() && binary.len() > 0)
+                .then_some(install)
+        })
+        .collect();
+
+    installs.sort_by(|a, b| b.version.cmp(&a.version));
+    installs
+}
+
+/// The install in `dir`, from either channel, if its binary
+/// is still there.
+pub fn find(dir: &str) -> Option<Install> {
+    [Channel::Stable, Channel::Prerelease]
+        .into_iter()
+        .flat_map(list)
+        .find(|i| i.dir == dir)
+}
+
+/// Remove installs from `channel` beyond the `keep` newest.
+///
+/// Directories named in `protected` (the fresh install and
+/// the binary a running server was started from) are never
+/// removed, whatever their age.
+pub fn prune(channel: Channel, keep: usize, protected: &[&str]) {
+    for install in list(channel).into_iter().skip(keep) {
+        if protected.contains(&install.dir.as_str()) {
+            continue;
+        }
+        if let Err(e) = fs::remove_dir_all(&install.dir) {
+            eprintln!(
+                "shebe: failed to remove old install {}: {}",
+                install.dir, e,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_do_not_share_installs() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in [
+            "shebe-v0.5.6",
+            "shebe-v0.5.7",
+            "shebe-prerelease-v0.6.0-rc.1",
+            "shebe-empty",
+        ] {
+            fs::create_dir(root.path().join(dir)).unwrap();
+            if dir != "shebe-empty" {
+                fs::write(root.path().join(dir).join("shebe-mcp"), "x")
+                    .unwrap();
+            }
+        }
+
+        let dirs = |channel| -> Vec<String> {
+            list_in(root.path(), channel)
+                .into_iter()
+                .map(|i| i.dir)
+                .collect()
+        };
+        assert_eq!(dirs(Channel::Stable), ["shebe-v0.5.7", "shebe-v0.5.6"]);
+        assert_eq!(
+            dirs(Channel::Prerelease),
+            ["shebe-prerelease-v0.6.0-rc.1"],
+        );
+    }
+}
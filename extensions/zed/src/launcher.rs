@@ -0,0 +1,854 @@
+//! Shell launcher for settings that depend on the worktree.
+//!
+//! `context_server_command` only receives a `Project`, which
+//! exposes worktree ids but not their paths. Anything
+//! derived from the worktree path is computed by a short
+//! `/bin/sh` prelude that runs in the server's working
+//! directory and then `exec`s `shebe-mcp`.
+//!
+//! This requires Zed to start context servers from the
+//! project root, which the extension API does not promise.
+//! The prelude refuses to treat `/` or `$HOME` as a project,
+//! the directories a server started from elsewhere would
+//! most likely see.
+//!
+//! Slash commands do know their worktree, so
+//! [`for_worktree`] applies the same settings in Rust and
+//! runs `shebe-mcp` without a launcher.
+
+use std::collections::BTreeMap;
+
+use zed_extension_api::{self as zed, serde_json::json};
+
+use crate::mcp;
+use crate::settings::{
+    self, Piece, Placeholder, ProjectDataDir, ShebeSettings,
+};
+
+/// Quote `s` as a single POSIX shell word.
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Run `prelude` in `/bin/sh` before `exec`ing `command`.
+///
+/// The binary is passed as `$0` and its arguments as `$@`,
+/// so neither needs quoting inside the script. An empty
+/// prelude returns `command` unchanged.
+pub fn wrap(
+    command: zed::Command,
+    prelude: &[String],
+) -> zed::Command {
+    if prelude.is_empty() {
+        return command;
+    }
+
+    let mut script = prelude.join("\n");
+    script.push_str("\nexec \"$0\" \"$@\"");
+
+    let mut args =
+        vec!["-c".to_string(), script, command.command];
+    args.extend(command.args);
+
+    zed::Command {
+        command: "/bin/sh".into(),
+        args,
+        env: command.env,
+    }
+}
+
+/// Prelude exporting a per-project `SHEBE_DATA_DIR`.
+///
+/// `Worktree` keeps the index in `.shebe/` at the project
+/// root. `Extension` keeps it under the extension work dir,
+/// in a directory named after the project and a checksum of
+/// its path so same-named checkouts do not collide. Started
+/// from `/` or `$HOME`, the server keeps the shared data dir
+/// rather than a per-project one for a directory that is
+/// not a project.
+pub fn data_dir(
+    mode: ProjectDataDir,
+    work_dir: &str,
+) -> Option<String> {
+    let dir = match mode {
+        ProjectDataDir::Off => return None,
+        ProjectDataDir::Worktree => "\"$PWD/.shebe\"".to_string(),
+        ProjectDataDir::Extension => format!(
+            "{}/data/\"$(basename \"$PWD\")-$(printf '%s' \
+             \"$PWD\" | cksum | cut -d' ' -f1)\"",
+            quote(work_dir),
+        ),
+    };
+    Some(format!(
+        "case \"$PWD\" in\n\
+         /|\"$HOME\") echo \"shebe: $PWD is not a project root; \
+         ignoring project_data_dir\" >&2 ;;\n\
+         *) SHEBE_DATA_DIR={}\n\
+         mkdir -p \"$SHEBE_DATA_DIR\"\n\
+         export SHEBE_DATA_DIR ;;\n\
+         esac",
+        dir,
+    ))
+}
+
+/// Apply the `args` and `env` settings to `command`.
+///
+/// Values that only use `${extension_dir}` are substituted
+/// here. `${worktree_root}` and `${home}` are only known to
+/// the launcher, so values using them are set by the
+/// returned prelude instead. Variables in `pinned` (those
+/// `command.env` sets) are left alone.
+pub fn extra_args_env(
+    command: &mut zed::Command,
+    args: &[String],
+    env: &BTreeMap<String, String>,
+    work_dir: &str,
+    pinned: &[String],
+) -> Result<Vec<String>, String> {
+    let mut prelude = Vec::new();
+    for (key, value) in env {
+        if pinned.contains(key) {
+            continue;
+        }
+        command.env.retain(|(k, _)| k != key);
+        match expand(value, work_dir)? {
+            Expanded::Literal(value) => {
+                command.env.push((key.clone(), value));
+            }
+            Expanded::Shell(word) => {
+                prelude.push(format!("export {}={}", key, word));
+            }
+        }
+    }
+
+    let args = args
+        .iter()
+        .map(|arg| expand(arg, work_dir))
+        .collect::<Result<Vec<_>, _>>()?;
+    let literal: Option<Vec<String>> = args
+        .iter()
+        .map(|a| match a {
+            Expanded::Literal(arg) => Some(arg.clone()),
+            Expanded::Shell(_) => None,
+        })
+        .collect();
+    if let Some(literal) = literal {
+        command.args.extend(literal);
+    } else {
+        // Keep the settings order: every argument goes
+        // through the launcher once one of them has to.
+        let words: Vec<String> = args
+            .into_iter()
+            .map(|a| match a {
+                Expanded::Literal(arg) => quote(&arg),
+                Expanded::Shell(word) => word,
+            })
+            .collect();
+        prelude.push(format!("set -- \"$@\" {}", words.join(" ")));
+    }
+    Ok(prelude)
+}
+
+/// A templated value after [`expand`].
+#[derive(Debug, PartialEq, Eq)]
+enum Expanded {
+    /// Fully substituted.
+    Literal(String),
+    /// A double-quoted shell word for the launcher.
+    Shell(String),
+}
+
+fn expand(value: &str, work_dir: &str) -> Result<Expanded, String> {
+    let pieces = settings::template(value)?;
+    let in_shell = pieces.iter().any(|p| {
+        matches!(
+            p,
+            Piece::Var(Placeholder::WorktreeRoot | Placeholder::Home)
+        )
+    });
+    if !in_shell {
+        return Ok(Expanded::Literal(
+            pieces
+                .iter()
+                .map(|p| match p {
+                    Piece::Text(text) => text,
+                    Piece::Var(_) => work_dir,
+                })
+                .collect(),
+        ));
+    }
+
+    let escape = |s: &str| {
+        s.chars().fold(String::new(), |mut out, c| {
+            if matches!(c, '\\' | '"' | '$' | '`') {
+                out.push('\\');
+            }
+            out.push(c);
+            out
+        })
+    };
+    let mut word = String::from('"');
+    for piece in pieces {
+        word.push_str(&match piece {
+            Piece::Text(text) => escape(text),
+            Piece::Var(Placeholder::ExtensionDir) => escape(work_dir),
+            Piece::Var(Placeholder::WorktreeRoot) => "$PWD".into(),
+            Piece::Var(Placeholder::Home) => "$HOME".into(),
+        });
+    }
+    word.push('"');
+    Ok(Expanded::Shell(word))
+}
+
+/// The command the launcher would run for the worktree at
+/// `root`, with the `project_data_dir`, `args` and `env`
+/// settings applied directly.
+///
+/// `home` is the user's `$HOME`, if known. Like the
+/// launcher, `/` and `home` never get a per-project data
+/// dir.
+pub fn for_worktree(
+    mut command: zed::Command,
+    shebe: &ShebeSettings,
+    pinned: &[String],
+    work_dir: &str,
+    root: &str,
+    home: Option<&str>,
+) -> Result<zed::Command, String> {
+    let root = match root.trim_end_matches('/') {
+        "" => "/",
+        root => root,
+    };
+    let is_project = root != "/" && Some(root) != home;
+    let data_dir = match shebe.project_data_dir {
+        ProjectDataDir::Off => None,
+        _ if !is_project => None,
+        ProjectDataDir::Worktree => Some(format!("{}/.shebe", root)),
+        ProjectDataDir::Extension => Some(format!(
+            "{}/data/{}-{}",
+            work_dir,
+            root.rsplit('/').next().unwrap_or_default(),
+            cksum(root.as_bytes()),
+        )),
+    };
+    if let Some(dir) = data_dir {
+        command.env.retain(|(k, _)| k != "SHEBE_DATA_DIR");
+        command.env.push(("SHEBE_DATA_DIR".into(), dir));
+    }
+
+    let substitute = |value: &str| -> Result<String, String> {
+        settings::template(value)?
+            .into_iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => Ok(text),
+                Piece::Var(Placeholder::ExtensionDir) => Ok(work_dir),
+                Piece::Var(Placeholder::WorktreeRoot) => Ok(root),
+                Piece::Var(Placeholder::Home) => home.ok_or_else(|| {
+                    format!("cannot expand ${{home}} in '{}'", value)
+                }),
+            })
+            .collect()
+    };
+    for (key, value) in &shebe.env {
+        if pinned.contains(key) {
+            continue;
+        }
+        command.env.retain(|(k, _)| k != key);
+        command.env.push((key.clone(), substitute(value)?));
+    }
+    for arg in &shebe.args {
+        command.args.push(substitute(arg)?);
+    }
+    Ok(command)
+}
+
+/// POSIX `cksum` CRC of `data`, as used for the
+/// [`ProjectDataDir::Extension`] directory names.
+fn cksum(data: &[u8]) -> u32 {
+    fn step(mut crc: u32, byte: u8) -> u32 {
+        crc ^= u32::from(byte) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    }
+
+    let mut crc = data.iter().fold(0, |crc, &b| step(crc, b));
+    let mut len = data.len();
+    while len > 0 {
+        crc = step(crc, len as u8);
+        len >>= 8;
+    }
+    !crc
+}
+
+/// Work dir subdirectory with one stamp file per
+/// auto-indexed worktree; its mtime is the last (re)index.
+pub const AUTO_INDEX_DIR: &str = "auto-index";
+
+/// Work dir file collecting the auto-index job's stderr.
+pub const AUTO_INDEX_LOG: &str = "auto-index.log";
+
+/// Background auto-index job, see [`auto_index`]. The
+/// session name must stay byte-for-byte what
+/// [`crate::slash::session_name`] computes.
+const AUTO_INDEX_SCRIPT: &str = r#"case "$PWD" in
+/|"$HOME") echo "shebe: $PWD is not a project root; not auto-indexing" >&2 ;;
+*)
+shebe_session=$(basename "$PWD" | LC_ALL=C tr -c 'A-Za-z0-9_\n-' '-')
+[ "$shebe_session" = - ] && shebe_session=root
+(
+shebe_call() {
+  printf '%s\n' @INITIALIZE@ @INITIALIZED@ \
+    "{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"tools/call\",\"params\":{\"name\":\"$1\",\"arguments\":$2}}" \
+    | "$0" 2>/dev/null | grep '"id":2' | grep -v '"isError":true' \
+    | grep -q '"result"'
+}
+stamp=@STAMP_DIR@/"$shebe_session-$(printf '%s' "$PWD" | cksum | cut -d' ' -f1)"
+session="{\"session\":\"$shebe_session\"}"
+if [ -e "$stamp" ]; then
+  @REINDEX@
+  exit 0
+fi
+mkdir -p @STAMP_DIR@
+if shebe_call get_session_info "$session"; then
+  touch "$stamp"
+  exit 0
+fi
+if git rev-parse --is-inside-work-tree >/dev/null 2>&1; then
+  kb=$(git ls-files -z -co --exclude-standard | xargs -0 du -k 2>/dev/null | awk '{ s += $1 } END { print s + 0 }')
+else
+  kb=$(du -sk . | cut -f1)
+fi
+if [ @MAX_KB@ -gt 0 ] && [ "$kb" -gt @MAX_KB@ ]; then
+  echo "shebe: not auto-indexing $PWD: $kb KiB is over auto_index_max_mb" >&2
+  exit 0
+fi
+excludes=$(cat .gitignore .shebeignore 2>/dev/null | awk '
+  function emit(p) { printf "%s\"%s\"", sep, p; sep = "," }
+  BEGIN { @EXCLUDE@ }
+  /^[[:space:]]*(#|!|$)/ { next }
+  {
+    sub(/[[:space:]]+$/, ""); p = $0
+    dir = sub(/\/$/, "", p); anchored = sub(/^\//, "", p)
+    if (!anchored && index(p, "/") == 0) p = "**/" p
+    gsub(/\\/, "\\\\", p); gsub(/"/, "\\\"", p)
+    emit(p)
+    n = split(p, parts, "/")
+    if (dir || parts[n] !~ /[*?[]/) emit(p "/**")
+  }')
+path=$(printf '%s' "$PWD" | sed 's/\\/\\\\/g; s/"/\\"/g')
+if shebe_call index_repository "{\"path\":\"$path\",\"session\":\"$shebe_session\",\"exclude_patterns\":[$excludes]}"; then
+  touch "$stamp"
+else
+  echo "shebe: auto-indexing $PWD into session $shebe_session failed" >&2
+fi
+) </dev/null >/dev/null 2>>@LOG@ &
+;;
+esac"#;
+
+/// Re-index step of [`AUTO_INDEX_SCRIPT`] for stamps older
+/// than `@MINUTES@`.
+const REINDEX_SCRIPT: &str = r#"if [ -n "$(find "$stamp" -mmin +@MINUTES@)" ]; then
+    if shebe_call reindex_session "$session"; then
+      touch "$stamp"
+    else
+      echo "shebe: re-indexing session $shebe_session failed" >&2
+    fi
+  fi"#;
+
+/// Prelude that indexes the worktree in the background.
+///
+/// A worktree without a session (named like the slash
+/// commands name it) and below `max_mb` of files git does
+/// not ignore is indexed with `index_repository`, excluding
+/// the patterns in `.gitignore` and `.shebeignore`. Once a
+/// stamp under [`AUTO_INDEX_DIR`] is `reindex_hours` old the
+/// session is refreshed with `reindex_session`. The job
+/// talks to its own `shebe-mcp`, never touches the server's
+/// stdin, stdout or stderr, and logs to [`AUTO_INDEX_LOG`].
+///
+/// The ignore files are translated on a best-effort basis:
+/// comments and blank lines are skipped, negations (`!`)
+/// are dropped, and a pattern without a `/` is matched at
+/// any depth. Unless its last component is a wildcard file
+/// glob such as `*.log`, a pattern may name a directory, so
+/// everything below it is excluded too. Anything else is
+/// passed to shebe's glob matcher as written, which may
+/// read `**` or character classes differently from git. A
+/// [`ProjectDataDir::Worktree`] index is always excluded.
+pub fn auto_index(
+    max_mb: u64,
+    reindex_hours: u64,
+    data_dir: ProjectDataDir,
+    work_dir: &str,
+) -> String {
+    let initialize = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": mcp::initialize_params(),
+    });
+    let initialized = json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/initialized",
+    });
+    let reindex = match reindex_hours {
+        0 => ":".to_string(),
+        hours => REINDEX_SCRIPT
+            .replace("@MINUTES@", &(hours * 60).to_string()),
+    };
+
+    AUTO_INDEX_SCRIPT
+        .replace("@REINDEX@", &reindex)
+        .replace("@INITIALIZE@", &quote(&initialize.to_string()))
+        .replace("@INITIALIZED@", &quote(&initialized.to_string()))
+        .replace(
+            "@STAMP_DIR@",
+            &quote(&format!("{}/{}", work_dir, AUTO_INDEX_DIR)),
+        )
+        .replace("@MAX_KB@", &(max_mb * 1024).to_string())
+        .replace(
+            "@EXCLUDE@",
+            match data_dir {
+                ProjectDataDir::Worktree => {
+                    r#"emit(".shebe"); emit(".shebe/**")"#
+                }
+                _ => "",
+            },
+        )
+        .replace(
+            "@LOG@",
+            &quote(&format!("{}/{}", work_dir, AUTO_INDEX_LOG)),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use std::process;
+
+    /// Stand-in `shebe-mcp` that logs its input to `$LOG`,
+    /// knows no sessions and succeeds every other call.
+    const FAKE_SERVER: &str = r#"#!/bin/sh
+while IFS= read -r line; do
+  printf '%s\n' "$line" >> "$LOG"
+  case "$line" in
+    *'"id":2'*get_session_info*)
+      echo '{"jsonrpc":"2.0","id":2,"result":{"isError":true}}' ;;
+    *'"id":2'*)
+      echo '{"jsonrpc":"2.0","id":2,"result":{"content":[]}}' ;;
+  esac
+done
+"#;
+
+    /// Run the auto-index prelude in `project` against the
+    /// fake server, wait for the job and return its input.
+    fn run(
+        tmp: &Path,
+        project: &Path,
+        max_mb: u64,
+        reindex_hours: u64,
+        data_dir: ProjectDataDir,
+    ) -> String {
+        let server = tmp.join("shebe-mcp");
+        fs::write(&server, FAKE_SERVER).unwrap();
+        fs::set_permissions(&server, fs::Permissions::from_mode(0o755))
+            .unwrap();
+        let log = tmp.join("input.log");
+        let _ = fs::remove_file(&log);
+        let work_dir = tmp.join("work");
+        fs::create_dir_all(&work_dir).unwrap();
+        let script = format!(
+            "{}\nwait",
+            auto_index(
+                max_mb,
+                reindex_hours,
+                data_dir,
+                work_dir.to_str().unwrap(),
+            ),
+        );
+        let status = process::Command::new("/bin/sh")
+            .arg("-c")
+            .arg(script)
+            .arg(&server)
+            .current_dir(project)
+            .env("LOG", &log)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        fs::read_to_string(log).unwrap_or_default()
+    }
+
+    fn project(tmp: &Path) -> std::path::PathBuf {
+        let project = tmp.join("My App");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(
+            project.join(".gitignore"),
+            "# build output\ntarget/\n*.log\n/dist\n!keep.log\n",
+        )
+        .unwrap();
+        project
+    }
+
+    /// `SHEBE_DATA_DIR` as the `mode` prelude sets it in
+    /// `dir` with `$HOME` at `home`.
+    fn data_dir_in(
+        mode: ProjectDataDir,
+        dir: &Path,
+        home: &Path,
+    ) -> String {
+        let script = format!(
+            "{}\nprintf '%s' \"${{SHEBE_DATA_DIR-unset}}\"",
+            data_dir(mode, "/ext").unwrap(),
+        );
+        let output = process::Command::new("/bin/sh")
+            .args(["-c", &script])
+            .current_dir(dir)
+            .env("HOME", home)
+            .env_remove("SHEBE_DATA_DIR")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn data_dir_is_per_project() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = project(tmp.path());
+        assert_eq!(
+            data_dir_in(ProjectDataDir::Worktree, &project, tmp.path()),
+            format!("{}/.shebe", project.display()),
+        );
+        assert!(project.join(".shebe").is_dir());
+        assert!(data_dir(ProjectDataDir::Off, "/ext").is_none());
+    }
+
+    #[test]
+    fn data_dir_ignores_home_and_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(
+            data_dir_in(ProjectDataDir::Worktree, tmp.path(), tmp.path()),
+            "unset",
+        );
+        assert_eq!(
+            data_dir_in(ProjectDataDir::Extension, Path::new("/"), tmp.path()),
+            "unset",
+        );
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        assert_eq!(
+            expand("${extension_dir}/logs", "/ext").unwrap(),
+            Expanded::Literal("/ext/logs".into()),
+        );
+        assert_eq!(
+            expand("--root=${worktree_root} \"$x\"", "/ext").unwrap(),
+            Expanded::Shell(r#""--root=$PWD \"\$x\"""#.into()),
+        );
+        assert!(expand("${nope}", "/ext")
+            .unwrap_err()
+            .contains("unknown placeholder ${nope}"));
+        assert!(expand("${home", "/ext").is_err());
+    }
+
+    #[test]
+    fn launcher_applies_args_and_env() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = project(tmp.path());
+        let server = tmp.path().join("shebe-mcp");
+        fs::write(
+            &server,
+            "#!/bin/sh\nprintf '%s\\n' \"$@\" \"$RUST_LOG\" \
+             \"$SHEBE_LOG\" \"$ROOT\" \"$PINNED\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&server, fs::Permissions::from_mode(0o755))
+            .unwrap();
+
+        let mut command = zed::Command {
+            command: server.to_str().unwrap().into(),
+            args: vec!["--stdio".into()],
+            env: vec![
+                ("RUST_LOG".into(), "info".into()),
+                ("PINNED".into(), "command".into()),
+            ],
+        };
+        let args = ["--log-level", "debug", "--home=${home}"]
+            .map(String::from);
+        let env = [
+            ("RUST_LOG", "debug"),
+            ("SHEBE_LOG", "${extension_dir}/log"),
+            ("ROOT", "${worktree_root}"),
+            ("PINNED", "settings"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let prelude = extra_args_env(
+            &mut command,
+            &args,
+            &env,
+            "/ext",
+            &["PINNED".into()],
+        )
+        .unwrap();
+        let command = wrap(command, &prelude);
+
+        let output = process::Command::new(&command.command)
+            .args(&command.args)
+            .envs(command.env)
+            .env("HOME", "/home/me")
+            .current_dir(&project)
+            .output()
+            .unwrap();
+        let lines: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        let root = project.to_str().unwrap().to_string();
+        assert_eq!(
+            lines,
+            [
+                "--stdio",
+                "--log-level",
+                "debug",
+                "--home=/home/me",
+                "debug",
+                "/ext/log",
+                &root,
+                "command",
+            ],
+        );
+    }
+
+    #[test]
+    fn worktree_command_matches_launcher() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("Mÿ App");
+        fs::create_dir(&project).unwrap();
+        let server = tmp.path().join("shebe-mcp");
+        fs::write(
+            &server,
+            "#!/bin/sh\nprintf '%s\\n' \"$@\" \"$SHEBE_DATA_DIR\" \
+             \"$ROOT\" \"$PINNED\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&server, fs::Permissions::from_mode(0o755))
+            .unwrap();
+
+        let shebe = ShebeSettings {
+            project_data_dir: ProjectDataDir::Extension,
+            args: vec!["--home=${home}".into()],
+            env: [("ROOT", "${worktree_root}"), ("PINNED", "settings")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..ShebeSettings::default()
+        };
+        let base = zed::Command {
+            command: server.to_str().unwrap().into(),
+            args: vec!["--stdio".into()],
+            env: vec![("PINNED".into(), "command".into())],
+        };
+        let pinned = ["PINNED".to_string()];
+        let work_dir = tmp.path().join("work");
+        let work_dir = work_dir.to_str().unwrap();
+        let output = |command: zed::Command| {
+            let output = process::Command::new(&command.command)
+                .args(&command.args)
+                .envs(command.env)
+                .env("HOME", "/home/me")
+                .current_dir(&project)
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        let mut launched = base.clone();
+        let mut prelude: Vec<String> =
+            data_dir(shebe.project_data_dir, work_dir)
+                .into_iter()
+                .collect();
+        prelude.extend(
+            extra_args_env(
+                &mut launched,
+                &shebe.args,
+                &shebe.env,
+                work_dir,
+                &pinned,
+            )
+            .unwrap(),
+        );
+        let launched = output(wrap(launched, &prelude));
+        let direct = output(
+            for_worktree(
+                base,
+                &shebe,
+                &pinned,
+                work_dir,
+                project.to_str().unwrap(),
+                Some("/home/me"),
+            )
+            .unwrap(),
+        );
+        assert_eq!(direct, launched);
+        assert!(direct.contains(&format!("{}/data/Mÿ App-", work_dir)));
+    }
+
+    #[test]
+    fn worktree_command_skips_data_dir_for_home() {
+        let shebe = ShebeSettings {
+            project_data_dir: ProjectDataDir::Worktree,
+            ..ShebeSettings::default()
+        };
+        let base = zed::Command {
+            command: "shebe-mcp".into(),
+            args: vec![],
+            env: vec![],
+        };
+        for root in ["/", "/home/me/"] {
+            let command = for_worktree(
+                base.clone(),
+                &shebe,
+                &[],
+                "/ext",
+                root,
+                Some("/home/me"),
+            )
+            .unwrap();
+            assert!(command.env.is_empty(), "{}", root);
+        }
+        let command =
+            for_worktree(base, &shebe, &[], "/ext", "/src/app", None)
+                .unwrap();
+        assert_eq!(
+            command.env,
+            [("SHEBE_DATA_DIR".into(), "/src/app/.shebe".into())],
+        );
+    }
+
+    #[test]
+    fn indexes_new_worktree_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = project(tmp.path());
+
+        let input = run(tmp.path(), &project, 500, 24, ProjectDataDir::Off);
+        assert!(input.contains(r#""name":"get_session_info""#));
+        assert!(input.contains(concat!(
+            r#""session":"My-App","exclude_patterns":["#,
+            r#""**/target","**/target/**","**/*.log","dist","dist/**"]"#,
+        )));
+        let stamps = fs::read_dir(tmp.path().join("work/auto-index"))
+            .unwrap()
+            .count();
+        assert_eq!(stamps, 1);
+
+        let input = run(tmp.path(), &project, 500, 24, ProjectDataDir::Off);
+        assert_eq!(input, "");
+    }
+
+    #[test]
+    fn excludes_worktree_data_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("app");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join(".gitignore"), "node_modules\n").unwrap();
+
+        let input =
+            run(tmp.path(), &project, 500, 24, ProjectDataDir::Worktree);
+        assert!(input.contains(concat!(
+            r#""exclude_patterns":[".shebe",".shebe/**","#,
+            r#""**/node_modules","**/node_modules/**"]"#,
+        )));
+    }
+
+    #[test]
+    fn session_name_matches_slash_commands() {
+        let tmp = tempfile::tempdir().unwrap();
+        for name in ["Mÿ App", "日本", "-"] {
+            let project = tmp.path().join(name);
+            fs::create_dir(&project).unwrap();
+            let input = run(tmp.path(), &project, 500, 24, ProjectDataDir::Off);
+            let session = crate::slash::session_name(
+                project.to_str().unwrap(),
+            );
+            assert!(
+                input.contains(&format!(r#""session":"{}""#, session)),
+                "{}: {}",
+                session,
+                input,
+            );
+        }
+    }
+
+    #[test]
+    fn skips_worktree_over_size_cap() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = project(tmp.path());
+        fs::write(project.join("blob"), vec![1u8; 2 << 20]).unwrap();
+
+        let input = run(tmp.path(), &project, 1, 24, ProjectDataDir::Off);
+        assert!(input.contains("get_session_info"));
+        assert!(!input.contains("index_repository"));
+        let log =
+            fs::read_to_string(tmp.path().join("work").join(AUTO_INDEX_LOG))
+                .unwrap();
+        assert!(log.contains("over auto_index_max_mb"), "{}", log);
+    }
+
+    #[test]
+    fn never_indexes_home() {
+        let tmp = tempfile::tempdir().unwrap();
+        let server = tmp.path().join("shebe-mcp");
+        let output = process::Command::new("/bin/sh")
+            .arg("-c")
+            .arg(format!(
+                "{}\nwait",
+                auto_index(500, 24, ProjectDataDir::Off, "/ext"),
+            ))
+            .arg(&server)
+            .current_dir(tmp.path())
+            .env("HOME", tmp.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("not a project root"));
+        assert!(!Path::new("/ext").exists());
+    }
+
+    #[test]
+    fn reindexes_stale_session() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = project(tmp.path());
+        run(tmp.path(), &project, 500, 24, ProjectDataDir::Off);
+        let stamp = fs::read_dir(tmp.path().join("work/auto-index"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let touched = process::Command::new("touch")
+            .args(["-t", "200001010000"])
+            .arg(&stamp)
+            .status()
+            .unwrap();
+        assert!(touched.success());
+
+        let input = run(tmp.path(), &project, 500, 0, ProjectDataDir::Off);
+        assert_eq!(input, "");
+        let input = run(tmp.path(), &project, 500, 24, ProjectDataDir::Off);
+        assert!(input.contains(r#""name":"reindex_session""#));
+    }
+}
@@ -1,130 +1,901 @@
-use std::env;
+pub mod checksum;
+pub mod error;
+mod health;
+mod installs;
+mod launcher;
+pub mod manifest;
+pub mod mcp;
+mod path_lookup;
+pub mod platform;
+pub mod release;
+mod settings;
+mod slash;
+pub mod source;
+mod state;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::{env, fs};
+
+use error::ShebeError;
+use flate2::read::GzDecoder;
+use platform::{Arch, Checksum, Libc, Os, Platform};
+use release::{Channel, ReleaseQuery, VersionSpec};
+use settings::ShebeSettings;
+use state::{Installed, State};
 use zed_extension_api::{
-    self as zed, ContextServerId, Project,
+    self as zed,
+    settings::{CommandSettings, ContextServerSettings},
+    ContextServerId, Project, SlashCommand,
+    SlashCommandArgumentCompletion, SlashCommandOutput, Worktree,
 };
 
 struct ShebeExtension {
-    cached_binary_path: Option<String>,
+    /// Installed binary and the query it was resolved from.
+    /// Changing the version pin or channel invalidates it.
+    cached_binary_path: Option<(ReleaseQuery, String)>,
+    /// How the context server was last started. Slash
+    /// commands run the same binary with the same settings
+    /// and index, resolved for their worktree rather than
+    /// through the launcher.
+    server: Option<Started>,
+    /// Worktree root and `$HOME` of the last slash command.
+    /// Argument completion gets no worktree, but
+    /// `list_sessions` has to read that worktree's index.
+    last_root: Mutex<Option<(String, Option<String>)>>,
+    /// `list_sessions` results by worktree root, so session
+    /// completion starts `shebe-mcp` once rather than per
+    /// keystroke. Cleared whenever sessions may have changed:
+    /// after `/shebe-index` and `/shebe-reindex`, and when the
+    /// context server (re)starts with possibly new settings.
+    sessions: Mutex<HashMap<String, Vec<String>>>,
+}
+
+/// The context server's `shebe-mcp` before the launcher
+/// prelude, and the settings the prelude applies.
+struct Started {
+    command: zed::Command,
+    settings: ShebeSettings,
+    pinned: Vec<String>,
 }
 
 impl ShebeExtension {
-    fn get_or_download_binary(&self) -> zed::Result<String> {
-        if let Some(path) = &self.cached_binary_path {
-            return Ok(path.clone());
+    fn get_or_download_binary(
+        &self,
+        settings: &ShebeSettings,
+    ) -> Result<String, ShebeError> {
+        let query = &settings.release_query();
+        let mut state = State::load();
+        if query.version == VersionSpec::Previous {
+            return previous_binary(&state);
         }
+        let active = state
+            .active
+            .as_ref()
+            .and_then(|i| installed_for(i, query));
 
-        let release = zed::latest_github_release(
-            "rhobimd-oss/shebe",
-            zed::GithubReleaseOptions {
-                require_assets: true,
-                pre_release: false,
-            },
-        )?;
+        // Between update checks, stay on what is installed
+        // without touching the network.
+        if !state.update_check_due(settings.update_check_hours) {
+            if let Some((cached, path)) = &self.cached_binary_path
+            {
+                if cached == query {
+                    return Ok(path.clone());
+                }
+            }
+            if let Some(install) = &active {
+                return Ok(install.binary_path());
+            }
+        }
 
-        let (os, arch) = zed::current_platform();
+        let release::Resolution { release, newest } =
+            match release::resolve(query) {
+                Ok(resolution) => resolution,
+                Err(e) => return offline_fallback(query, &state, e),
+            };
+        state.last_update_check = Some(state::now());
 
-        let os_str = match os {
-            zed::Os::Mac => "darwin",
-            zed::Os::Linux => "linux",
-            zed::Os::Windows => {
-                return Err(
-                    "shebe does not support Windows".into()
+        let extract_dir = format!(
+            "{}{}",
+            query.channel.dir_prefix(),
+            release.version,
+        );
+        let installed = match &active {
+            Some(install) if install.dir == extract_dir => {
+                Ok(install.binary_path())
+            }
+            _ => install_release(
+                release.clone(),
+                &extract_dir,
+                query,
+            ),
+        };
+        let binary_path = match (installed, &active) {
+            (Ok(path), _) => path,
+            // A new release that fails its health check is
+            // abandoned in favour of the install it would
+            // have replaced.
+            (
+                Err(ShebeError::HealthCheck { error, .. }),
+                Some(current),
+            ) => {
+                let notice = format!(
+                    "shebe-mcp {} failed its health check ({}); \
+                     rolled back to v{}.",
+                    release.version, error, current.version,
                 );
+                eprintln!("shebe: {}", notice);
+                state.notice = Some(notice);
+                state.save();
+                return Ok(current.binary_path());
             }
+            (Err(e), _) => return Err(e),
         };
 
-        let arch_str = match arch {
-            zed::Architecture::Aarch64 => {
-                if os_str == "linux" {
-                    return Err(
-                        "shebe does not support Linux ARM"
-                            .into()
-                    );
-                }
-                "aarch64"
+        state.notice = update_notice(
+            query,
+            active.as_ref(),
+            &release.version,
+            newest.as_deref(),
+        );
+        if let Some(notice) = &state.notice {
+            eprintln!("shebe: {}", notice);
+        }
+        let installed = Installed {
+            dir: extract_dir.clone(),
+            libc: query.libc,
+            source: query.source.to_string(),
+        };
+        let replaced = state
+            .active
+            .as_ref()
+            .is_some_and(|a| a.dir != extract_dir);
+        if replaced {
+            state.previous = state.active.take();
+        }
+        state.active = Some(installed);
+        state.save();
+
+        let running = self
+            .cached_binary_path
+            .as_ref()
+            .and_then(|(_, path)| path.split('/').next());
+        let previous =
+            state.previous.as_ref().map(|p| p.dir.as_str());
+        let protected: Vec<&str> =
+            [Some(extract_dir.as_str()), running, previous]
+                .into_iter()
+                .flatten()
+                .collect();
+        installs::prune(
+            query.channel,
+            settings.keep_versions,
+            &protected,
+        );
+
+        Ok(binary_path)
+    }
+
+    /// Resolve the `shebe-mcp` command: explicit
+    /// `command.path`, then `binary_path`, then `PATH`, then
+    /// a managed download.
+    fn server_command(
+        &mut self,
+        command: Option<CommandSettings>,
+        shebe: &ShebeSettings,
+    ) -> zed::Result<zed::Command> {
+        let env = shebe.tuning_env();
+        if let Some(command) = user_command(command, env.clone())
+        {
+            return Ok(command);
+        }
+        if let Some(path) = &shebe.binary_path {
+            return Ok(zed::Command {
+                command: path.clone(),
+                args: vec![],
+                env,
+            });
+        }
+
+        // Package managers only ship stable builds, so the
+        // pre-release channel always uses a managed download,
+        // as does a rollback to the previous managed install.
+        if shebe.path_lookup
+            && shebe.channel == Channel::Stable
+            && shebe.version != VersionSpec::Previous
+        {
+            if let Some(path) = path_lookup::find(&shebe.version) {
+                return Ok(zed::Command {
+                    command: path,
+                    args: vec![],
+                    env,
+                });
             }
-            zed::Architecture::X8664 => "x86_64",
-            zed::Architecture::X86 => {
-                return Err(
-                    "shebe does not support 32-bit x86"
-                        .into()
-                );
+        }
+
+        let binary_path = self.get_or_download_binary(shebe)?;
+        self.cached_binary_path =
+            Some((shebe.release_query(), binary_path.clone()));
+
+        let full_path = format!("{}/{}", work_dir()?, binary_path);
+
+        Ok(zed::Command {
+            command: full_path,
+            args: vec![],
+            env,
+        })
+    }
+
+    /// The `shebe-mcp` to run slash commands for the
+    /// worktree at `root` with. Slash commands see no project
+    /// settings, so they follow the context server, then the
+    /// active managed install, then `PATH`.
+    fn slash_server(
+        &self,
+        root: &str,
+        home: Option<&str>,
+        worktree: Option<&Worktree>,
+    ) -> Result<zed::Command, String> {
+        if let Some(started) = &self.server {
+            return launcher::for_worktree(
+                started.command.clone(),
+                &started.settings,
+                &started.pinned,
+                &work_dir()?,
+                root,
+                home,
+            );
+        }
+        let installed = State::load()
+            .active
+            .and_then(|active| installs::find(&active.dir));
+        let command = match installed {
+            Some(install) => format!(
+                "{}/{}",
+                work_dir()?,
+                install.binary_path(),
+            ),
+            None => worktree
+                .and_then(|w| w.which("shebe-mcp"))
+                .ok_or(
+                    "shebe-mcp is not installed yet; enable the \
+                     shebe context server in the Agent Panel \
+                     first",
+                )?,
+        };
+        Ok(zed::Command {
+            command,
+            args: vec![],
+            env: vec![],
+        })
+    }
+
+    /// Sessions to complete `/shebe-refs` with, from the
+    /// cache when possible.
+    ///
+    /// Argument completion gets no worktree. The root of the
+    /// last slash command is used; before there is one,
+    /// sessions are only listed when the server's settings do
+    /// not depend on the worktree.
+    fn session_names(&self) -> Vec<String> {
+        let last_root = self
+            .last_root
+            .lock()
+            .ok()
+            .and_then(|root| root.clone());
+        let (root, home) = match last_root {
+            Some(last_root) => last_root,
+            None if self
+                .server
+                .as_ref()
+                .is_some_and(|s| s.settings.uses_worktree()) =>
+            {
+                return Vec::new();
             }
+            None => ("/".to_string(), None),
         };
 
-        let suffix = if os_str == "linux" {
-            "-musl"
-        } else {
-            ""
+        let cached = self
+            .sessions
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(&root).cloned());
+        if let Some(cached) = cached {
+            return cached;
+        }
+        // Failures are cached too: retrying on every
+        // keystroke would not make shebe-mcp appear.
+        let names = self
+            .slash_server(&root, home.as_deref(), None)
+            .ok()
+            .and_then(|server| {
+                mcp::call(&server, &mcp::tools::ListSessions {}).ok()
+            })
+            .map(|text| slash::session_names(&text))
+            .unwrap_or_default();
+        if let Ok(mut cache) = self.sessions.lock() {
+            cache.insert(root, names.clone());
+        }
+        names
+    }
+
+    fn forget_sessions(&self) {
+        if let Ok(mut cache) = self.sessions.lock() {
+            cache.clear();
+        }
+    }
+}
+
+/// Describe the host for [`platform::resolve`]. The glibc
+/// probe spawns a process, so it only runs when `libc` is
+/// left on `"auto"` on Linux.
+fn host_platform(libc: Libc) -> Platform {
+    let (os, arch) = zed::current_platform();
+    let os = match os {
+        zed::Os::Mac => Os::Darwin,
+        zed::Os::Linux => Os::Linux,
+        zed::Os::Windows => Os::Windows,
+    };
+    let arch = match arch {
+        zed::Architecture::Aarch64 => Arch::Aarch64,
+        zed::Architecture::X8664 => Arch::X86_64,
+        zed::Architecture::X86 => Arch::X86,
+    };
+    let glibc = os == Os::Linux
+        && libc == Libc::Auto
+        && path_lookup::host_is_glibc();
+    Platform { os, arch, glibc }
+}
+
+/// Absolute host path of the extension work dir.
+fn work_dir() -> Result<String, ShebeError> {
+    env::current_dir()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .map_err(|e| ShebeError::Permission {
+            path: "extension work dir".into(),
+            error: e.to_string(),
+        })
+}
+
+/// Download a release asset to `path` in the work dir.
+fn download(url: &str, path: &str) -> Result<(), ShebeError> {
+    zed::download_file(
+        url,
+        path,
+        zed::DownloadedFileType::Uncompressed,
+    )
+    .map_err(|error| ShebeError::Download {
+        url: url.to_string(),
+        error,
+    })
+}
+
+/// The install recorded as `previous`, for
+/// `version: "previous"`.
+fn previous_binary(state: &State) -> Result<String, ShebeError> {
+    state
+        .previous
+        .as_ref()
+        .and_then(|p| installs::find(&p.dir))
+        .map(|i| i.binary_path())
+        .ok_or(ShebeError::NoPreviousInstall)
+}
+
+/// Resolve the asset for this host in `release` and install
+/// it into `extract_dir`.
+fn install_release(
+    release: platform::Release,
+    extract_dir: &str,
+    query: &ReleaseQuery,
+) -> Result<String, ShebeError> {
+    let platform = host_platform(query.libc);
+    let resolved =
+        platform::resolve(&release, platform, query.libc)?;
+    install_checked(&resolved, extract_dir, &release.version)
+}
+
+/// The recorded install, if it still exists and satisfies
+/// `query`.
+fn installed_for(
+    installed: &Installed,
+    query: &ReleaseQuery,
+) -> Option<installs::Install> {
+    if installed.libc != query.libc
+        || installed.source != query.source.to_string()
+    {
+        return None;
+    }
+    installs::list(query.channel).into_iter().find(|i| {
+        i.dir == installed.dir && query.version.matches(&i.version)
+    })
+}
+
+/// Describe the outcome of an update check: an update that
+/// was just applied, or a newer release (`newest`, from the
+/// same lookup) the `version` pin holds back.
+fn update_notice(
+    query: &ReleaseQuery,
+    previous: Option<&installs::Install>,
+    tag: &str,
+    newest: Option<&str>,
+) -> Option<String> {
+    let current =
+        semver::Version::parse(tag.trim_start_matches('v')).ok()?;
+
+    if query.version != VersionSpec::Latest {
+        let held_back = newest.filter(|newest| {
+            semver::Version::parse(newest.trim_start_matches('v'))
+                .is_ok_and(|v| v > current)
+        });
+        if let Some(newest) = held_back {
+            return Some(format!(
+                "shebe-mcp {} is available; the `version` \
+                 setting keeps this project on {}.",
+                newest, tag,
+            ));
+        }
+    }
+
+    let previous = previous?;
+    (previous.version != current).then(|| {
+        format!(
+            "Updated shebe-mcp from v{} to {}.",
+            previous.version, tag,
+        )
+    })
+}
+
+/// How often a freshly installed binary may fail its health
+/// check before the install is abandoned.
+const INSTALL_ATTEMPTS: usize = 2;
+
+/// Install `resolved` into `extract_dir` and health check the
+/// result. A binary that fails the check is quarantined and
+/// downloaded once more before giving up.
+fn install_checked(
+    resolved: &platform::ResolvedAsset,
+    extract_dir: &str,
+    tag: &str,
+) -> Result<String, ShebeError> {
+    let mut attempt = 1;
+    loop {
+        let binary_path = install(resolved, extract_dir)?;
+        let full_path = format!("{}/{}", work_dir()?, binary_path);
+        let error = match health::check(&full_path, tag) {
+            Ok(()) => return Ok(binary_path),
+            Err(error) => error,
         };
 
-        let asset_name = format!(
-            "shebe-{}-{}-{}{}.tar.gz",
-            release.version, os_str, arch_str, suffix,
+        health::quarantine(extract_dir);
+        if attempt == INSTALL_ATTEMPTS {
+            return Err(ShebeError::HealthCheck {
+                binary: binary_path,
+                error,
+            });
+        }
+        eprintln!(
+            "shebe: {} failed its health check ({}); \
+             downloading it again",
+            binary_path, error,
         );
+        attempt += 1;
+    }
+}
 
-        let asset = release
-            .assets
-            .iter()
-            .find(|a| a.name == asset_name)
-            .ok_or_else(|| {
-                format!(
-                    "no release asset matching '{}'",
-                    asset_name,
-                )
-            })?;
+/// Download, verify and unpack `resolved` into `extract_dir`.
+/// Returns the binary path relative to the work dir.
+fn install(
+    resolved: &platform::ResolvedAsset,
+    extract_dir: &str,
+) -> Result<String, ShebeError> {
+    let archive_path = format!("{}.tar.gz", extract_dir);
+    let checksum_path = format!("{}.sha256", archive_path);
+    let name = &resolved.archive.name;
 
-        let extract_dir = format!(
-            "shebe-{}",
-            release.version,
-        );
+    if let Checksum::Sidecar(sidecar) = &resolved.checksum {
+        download(&sidecar.download_url, &checksum_path)?;
+    }
+    download(&resolved.archive.download_url, &archive_path)?;
 
-        zed::download_file(
-            &asset.download_url,
-            &extract_dir,
-            zed::DownloadedFileType::GzipTar,
-        )?;
+    let installed = match &resolved.checksum {
+        Checksum::Sidecar(_) => {
+            checksum::verify(&archive_path, &checksum_path, name)
+        }
+        Checksum::Sha256(digest) => {
+            checksum::verify_digest(&archive_path, name, digest)
+        }
+    }
+    .and_then(|()| unpack_archive(&archive_path, extract_dir));
 
-        let binary_path = format!(
-            "{}/shebe-mcp",
-            extract_dir,
-        );
+    fs::remove_file(&archive_path).ok();
+    fs::remove_file(&checksum_path).ok();
+    installed?;
 
-        zed::make_file_executable(&binary_path)?;
+    let binary_path = format!("{}/shebe-mcp", extract_dir);
+    zed::make_file_executable(&binary_path).map_err(|e| {
+        ShebeError::Permission {
+            path: binary_path.clone(),
+            error: e,
+        }
+    })?;
+    Ok(binary_path)
+}
 
-        Ok(binary_path)
+/// Start the newest install matching `query` when the
+/// release lookup failed (no network, GitHub outage, rate
+/// limit). See [`fallback_install`] for which installs
+/// qualify. Returns the lookup error if nothing usable is
+/// installed.
+fn offline_fallback(
+    query: &ReleaseQuery,
+    state: &State,
+    error: String,
+) -> Result<String, ShebeError> {
+    let installs = installs::list(query.channel);
+    let install = fallback_install(query, state, &installs)
+        .ok_or_else(|| ShebeError::ReleaseLookup {
+            error: error.clone(),
+        })?;
+
+    eprintln!(
+        "shebe: release lookup failed ({}); starting \
+         installed {} instead",
+        error, install.dir,
+    );
+    Ok(install.binary_path())
+}
+
+/// The newest of `installs` (from `query.channel`) that
+/// `query` may start offline.
+///
+/// Installs recorded in `state` qualify when their `libc`
+/// and `release_source` match, like in [`installed_for`], so
+/// a musl or fork build is never started by accident. Only
+/// if none does are unrecorded `shebe-v*` dirs considered:
+/// those predate `state.json` and were always stable, musl
+/// builds from the upstream repository.
+fn fallback_install(
+    query: &ReleaseQuery,
+    state: &State,
+    installs: &[installs::Install],
+) -> Option<installs::Install> {
+    let recorded: Vec<&Installed> =
+        [state.active.as_ref(), state.previous.as_ref()]
+            .into_iter()
+            .flatten()
+            .collect();
+    let newest = |candidates: Vec<&installs::Install>| {
+        candidates
+            .into_iter()
+            .filter(|i| query.version.matches(&i.version))
+            .max_by(|a, b| a.version.cmp(&b.version))
+            .cloned()
+    };
+
+    let matching = recorded
+        .iter()
+        .filter(|r| {
+            r.libc == query.libc && r.source == query.source.to_string()
+        })
+        .filter_map(|r| installs.iter().find(|i| i.dir == r.dir))
+        .collect();
+    if let Some(install) = newest(matching) {
+        return Some(install);
     }
+
+    let baseline = query.channel == Channel::Stable
+        && matches!(query.libc, Libc::Auto | Libc::Musl)
+        && query.source == source::ReleaseSource::default();
+    if !baseline {
+        return None;
+    }
+    newest(
+        installs
+            .iter()
+            .filter(|i| !recorded.iter().any(|r| r.dir == i.dir))
+            .collect(),
+    )
+}
+
+/// Unpack a verified `.tar.gz` archive into `dest`.
+///
+/// Extraction happens inside the extension rather than via
+/// `zed::download_file` so the archive can be checksummed
+/// before any of its contents touch the work dir.
+fn unpack_archive(
+    archive_path: &str,
+    dest: &str,
+) -> Result<(), ShebeError> {
+    let file = fs::File::open(archive_path).map_err(|e| {
+        ShebeError::Permission {
+            path: archive_path.to_string(),
+            error: e.to_string(),
+        }
+    })?;
+
+    fs::remove_dir_all(dest).ok();
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    archive.unpack(dest).map_err(|e| {
+        fs::remove_dir_all(dest).ok();
+        ShebeError::Extraction {
+            archive: archive_path.to_string(),
+            error: e.to_string(),
+        }
+    })
+}
+
+/// Build a command from a user-supplied `command` block in
+/// `context_servers.<id>`, if it names a binary path.
+///
+/// The path, arguments and environment are passed through
+/// verbatim so local builds, Homebrew installs and vendored
+/// copies all work without the extension downloading its own.
+/// `command.env` is layered over `env` computed from the
+/// extension settings. Like any other command, the result
+/// is still wrapped by the [`launcher`] when a setting needs
+/// the worktree path.
+fn user_command(
+    settings: Option<CommandSettings>,
+    env: Vec<(String, String)>,
+) -> Option<zed::Command> {
+    let settings = settings?;
+    let path = settings.path.filter(|p| !p.is_empty())?;
+
+    let mut overrides: Vec<(String, String)> = settings
+        .env
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    overrides.sort();
+
+    Some(zed::Command {
+        command: path,
+        args: settings.arguments.unwrap_or_default(),
+        env: merge_env(env, overrides),
+    })
+}
+
+/// Variables `command.env` sets, which take precedence over
+/// the `env` setting. Like [`user_command`], only honoured
+/// when `command.path` is set.
+fn pinned_env(settings: Option<&CommandSettings>) -> Vec<String> {
+    settings
+        .filter(|s| s.path.as_deref().is_some_and(|p| !p.is_empty()))
+        .and_then(|s| s.env.as_ref())
+        .map(|env| env.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Append `overrides` to `base`, replacing any variable
+/// `base` already sets.
+fn merge_env(
+    mut base: Vec<(String, String)>,
+    overrides: Vec<(String, String)>,
+) -> Vec<(String, String)> {
+    base.retain(|(key, _)| {
+        !overrides.iter().any(|(k, _)| k == key)
+    });
+    base.extend(overrides);
+    base
 }
 
 impl zed::Extension for ShebeExtension {
     fn new() -> Self {
         Self {
             cached_binary_path: None,
+            server: None,
+            last_root: Mutex::new(None),
+            sessions: Mutex::new(HashMap::new()),
         }
     }
 
     fn context_server_command(
+        &mut self,
+        context_server_id: &ContextServerId,
+        project: &Project,
+    ) -> zed::Result<zed::Command> {
+        let settings = ContextServerSettings::for_project(
+            context_server_id.as_ref(),
+            project,
+        )?;
+        let shebe = ShebeSettings::from_value(settings.settings)?;
+        let pinned = pinned_env(settings.command.as_ref());
+        shebe.check_pinned(&pinned)?;
+        let mut command =
+            self.server_command(settings.command, &shebe)?;
+        self.forget_sessions();
+        self.server = Some(Started {
+            command: command.clone(),
+            settings: shebe.clone(),
+            pinned: pinned.clone(),
+        });
+
+        let work_dir = work_dir()?;
+        let mut prelude: Vec<String> =
+            launcher::data_dir(shebe.project_data_dir, &work_dir)
+                .into_iter()
+                .collect();
+        prelude.extend(launcher::extra_args_env(
+            &mut command,
+            &shebe.args,
+            &shebe.env,
+            &work_dir,
+            &pinned,
+        )?);
+        if shebe.auto_index {
+            prelude.push(launcher::auto_index(
+                shebe.auto_index_max_mb,
+                shebe.auto_reindex_hours,
+                shebe.project_data_dir,
+                &work_dir,
+            ));
+        }
+        Ok(launcher::wrap(command, &prelude))
+    }
+
+    fn context_server_configuration(
         &mut self,
         _context_server_id: &ContextServerId,
         _project: &Project,
-    ) -> zed::Result<zed::Command> {
-        let binary_path =
-            self.get_or_download_binary()?;
-        self.cached_binary_path =
-            Some(binary_path.clone());
+    ) -> zed::Result<Option<zed::ContextServerConfiguration>> {
+        let mut installation_instructions = include_str!(
+            "../configuration/installation_instructions.md"
+        )
+        .to_string();
+        if let Some(notice) = State::load().notice {
+            installation_instructions.push_str(&format!(
+                "\n## Update status\n\n{}\n",
+                notice,
+            ));
+        }
 
-        let full_path = env::current_dir()
-            .unwrap()
-            .join(&binary_path)
-            .to_string_lossy()
-            .to_string();
+        Ok(Some(zed::ContextServerConfiguration {
+            installation_instructions,
+            default_settings: include_str!(
+                "../configuration/default_settings.jsonc"
+            )
+            .to_string(),
+            settings_schema: include_str!(
+                "../configuration/settings_schema.json"
+            )
+            .to_string(),
+        }))
+    }
 
-        Ok(zed::Command {
-            command: full_path,
-            args: vec![],
-            env: vec![],
-        })
+    fn run_slash_command(
+        &self,
+        command: SlashCommand,
+        args: Vec<String>,
+        worktree: Option<&Worktree>,
+    ) -> zed::Result<SlashCommandOutput> {
+        let worktree =
+            worktree.ok_or("shebe commands need an open worktree")?;
+        let root = worktree.root_path();
+        let home = worktree
+            .shell_env()
+            .into_iter()
+            .find(|(key, _)| key == "HOME")
+            .map(|(_, home)| home);
+        let server =
+            self.slash_server(&root, home.as_deref(), Some(worktree))?;
+        if let Ok(mut last_root) = self.last_root.lock() {
+            *last_root = Some((root.clone(), home));
+        }
+        match command.name.as_str() {
+            "shebe" => slash::search(&server, &root, &args),
+            "shebe-index" | "shebe-reindex" => {
+                let reindex = command.name == "shebe-reindex";
+                let output = slash::index(&server, &root, reindex);
+                self.forget_sessions();
+                output
+            }
+            "shebe-refs" => {
+                let output =
+                    slash::references(&server, &root, &args)?;
+                let mut state = State::load();
+                state.remember_symbol(&args[0]);
+                state.save();
+                Ok(output)
+            }
+            name => Err(format!("unknown slash command /{}", name)),
+        }
+    }
+
+    fn complete_slash_command_argument(
+        &self,
+        command: SlashCommand,
+        args: Vec<String>,
+    ) -> zed::Result<Vec<SlashCommandArgumentCompletion>> {
+        if command.name != "shebe-refs" {
+            return Ok(Vec::new());
+        }
+        // Listing sessions starts shebe-mcp, so only do it
+        // once the symbol has been typed.
+        let sessions = match args.len() {
+            2 => self.session_names(),
+            _ => Vec::new(),
+        };
+        Ok(slash::reference_completions(
+            &args,
+            &State::load().recent_symbols,
+            &sessions,
+        ))
     }
 }
 
 zed::register_extension!(ShebeExtension);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(dir: &str) -> installs::Install {
+        installs::Install {
+            version: dir
+                .trim_start_matches("shebe-v")
+                .parse()
+                .unwrap(),
+            dir: dir.into(),
+        }
+    }
+
+    fn recorded(dir: &str, libc: Libc) -> Option<Installed> {
+        Some(Installed {
+            dir: dir.into(),
+            libc,
+            source: source::ReleaseSource::default().to_string(),
+        })
+    }
+
+    #[test]
+    fn falls_back_to_unrecorded_installs_without_state() {
+        let installs = [install("shebe-v0.5.7"), install("shebe-v0.5.6")];
+        let query = ReleaseQuery::default();
+        let chosen =
+            fallback_install(&query, &State::default(), &installs);
+        assert_eq!(chosen.unwrap().dir, "shebe-v0.5.7");
+
+        let pinned = ReleaseQuery {
+            version: "v0.5.6".parse().unwrap(),
+            ..ReleaseQuery::default()
+        };
+        let chosen =
+            fallback_install(&pinned, &State::default(), &installs);
+        assert_eq!(chosen.unwrap().dir, "shebe-v0.5.6");
+
+        for query in [
+            ReleaseQuery {
+                libc: Libc::Gnu,
+                ..ReleaseQuery::default()
+            },
+            ReleaseQuery {
+                source: "acme/shebe-fork".parse().unwrap(),
+                ..ReleaseQuery::default()
+            },
+        ] {
+            assert!(fallback_install(&query, &State::default(), &installs)
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn prefers_recorded_installs_and_skips_mismatched_ones() {
+        let installs = [install("shebe-v0.5.7"), install("shebe-v0.5.6")];
+        let state = State {
+            active: recorded("shebe-v0.5.7", Libc::Gnu),
+            previous: recorded("shebe-v0.5.6", Libc::Auto),
+            ..State::default()
+        };
+        let chosen =
+            fallback_install(&ReleaseQuery::default(), &state, &installs);
+        assert_eq!(chosen.unwrap().dir, "shebe-v0.5.6");
+
+        let state = State {
+            active: recorded("shebe-v0.5.7", Libc::Gnu),
+            ..State::default()
+        };
+        let chosen =
+            fallback_install(&ReleaseQuery::default(), &state, &installs);
+        assert_eq!(chosen.unwrap().dir, "shebe-v0.5.6");
+    }
+}
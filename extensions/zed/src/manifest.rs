@@ -0,0 +1,313 @@
+//! The `releases.json` release manifest.
+//!
+//! A static alternative to the GitHub releases API, served
+//! by mirrors (see [`crate::source`]) and generated from a
+//! directory of release artifacts by
+//! `tools/release-manifest`. Format, version 1:
+//!
+//! ```json
+//! {
+//!   "schema_version": 1,
+//!   "releases": [
+//!     {
+//!       "version": "v0.5.7",
+//!       "prerelease": false,
+//!       "min_extension_version": "0.5.0",
+//!       "targets": {
+//!         "linux-x86_64-musl": {
+//!           "url": "v0.5.7/shebe-v0.5.7-linux-x86_64-musl.tar.gz",
+//!           "sha256": "<64 hex digits>"
+//!         }
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Target keys are the `{os}-{arch}{suffix}` part of the
+//! GitHub asset names, so a manifest release resolves
+//! through [`crate::platform`] exactly like a GitHub one.
+//! `url` may be absolute or relative to the manifest.
+
+use std::collections::BTreeMap;
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use zed_extension_api::serde_json;
+
+use crate::platform::{Asset, Release};
+
+/// Conventional manifest file name at the root of a mirror.
+pub const MANIFEST_FILE: &str = "releases.json";
+
+/// Newest manifest format this extension understands.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Version of this extension, compared against
+/// `min_extension_version`.
+pub const EXTENSION_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub schema_version: u32,
+    /// Releases, newest first by convention; consumers sort
+    /// by version themselves.
+    pub releases: Vec<ManifestRelease>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestRelease {
+    /// Release tag, e.g. `v0.5.7`.
+    pub version: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    /// Oldest extension able to install this release.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_extension_version: Option<String>,
+    /// Archives by target, e.g. `darwin-aarch64` or
+    /// `linux-x86_64-musl`.
+    pub targets: BTreeMap<String, Target>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Target {
+    /// Archive URL, absolute or relative to the manifest.
+    pub url: String,
+    /// Lowercase hex SHA256 of the archive.
+    pub sha256: String,
+}
+
+impl Manifest {
+    /// Parse and validate a manifest document.
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| format!("invalid release manifest: {}", e))?;
+        if value.get("schema_version").is_none() {
+            // The first mirror format listed `assets` per
+            // release and had no version field.
+            return Err("release manifest has no schema_version; \
+                        regenerate it with tools/release-manifest"
+                .to_string());
+        }
+        let manifest: Self = serde_json::from_value(value)
+            .map_err(|e| format!("invalid release manifest: {}", e))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.schema_version == 0
+            || self.schema_version > SCHEMA_VERSION
+        {
+            return Err(format!(
+                "unsupported release manifest schema_version {} \
+                 (this extension reads up to {})",
+                self.schema_version, SCHEMA_VERSION,
+            ));
+        }
+        for release in &self.releases {
+            Version::parse(release.version.trim_start_matches('v'))
+                .map_err(|e| {
+                    format!(
+                        "release '{}' is not a version: {}",
+                        release.version, e,
+                    )
+                })?;
+            if let Some(min) = &release.min_extension_version {
+                Version::parse(min).map_err(|e| {
+                    format!(
+                        "release '{}' has an invalid \
+                         min_extension_version '{}': {}",
+                        release.version, min, e,
+                    )
+                })?;
+            }
+            for (target, entry) in &release.targets {
+                if !is_sha256(&entry.sha256) {
+                    return Err(format!(
+                        "release '{}' target '{}' has an invalid \
+                         sha256",
+                        release.version, target,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ManifestRelease {
+    /// Whether an extension at `extension_version` may
+    /// install this release.
+    pub fn supports(&self, extension_version: &Version) -> bool {
+        self.min_extension_version
+            .as_deref()
+            .and_then(|min| Version::parse(min).ok())
+            .is_none_or(|min| *extension_version >= min)
+    }
+
+    /// The release as seen by the resolver. Archive names
+    /// follow the GitHub convention and carry their digest,
+    /// so no `.sha256` sidecar is needed.
+    pub fn to_release(&self, manifest_url: &str) -> Release {
+        Release {
+            version: self.version.clone(),
+            assets: self
+                .targets
+                .iter()
+                .map(|(target, entry)| Asset {
+                    name: asset_name(&self.version, target),
+                    download_url: join_url(manifest_url, &entry.url),
+                    sha256: Some(entry.sha256.to_ascii_lowercase()),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Archive name of `target` in release `tag`.
+pub fn asset_name(tag: &str, target: &str) -> String {
+    format!("shebe-{}-{}.tar.gz", tag, target)
+}
+
+/// The target of an archive named per the GitHub
+/// convention, e.g. `linux-x86_64-musl` for
+/// `shebe-v0.5.7-linux-x86_64-musl.tar.gz`.
+pub fn target_of<'a>(tag: &str, name: &'a str) -> Option<&'a str> {
+    name.strip_prefix("shebe-")?
+        .strip_prefix(tag)?
+        .strip_prefix('-')?
+        .strip_suffix(".tar.gz")
+        .filter(|target| !target.is_empty())
+}
+
+/// Resolve `url` against the directory of `manifest_url`.
+pub fn join_url(manifest_url: &str, url: &str) -> String {
+    if url.contains("://") {
+        return url.to_string();
+    }
+    let base = manifest_url
+        .rsplit_once('/')
+        .map_or(manifest_url, |(dir, _)| dir);
+    format!("{}/{}", base, url.trim_start_matches('/'))
+}
+
+fn is_sha256(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str =
+        "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    fn manifest(schema_version: u32) -> String {
+        format!(
+            r#"{{
+              "schema_version": {},
+              "releases": [{{
+                "version": "v0.5.7",
+                "min_extension_version": "0.5.0",
+                "targets": {{
+                  "linux-x86_64-musl": {{
+                    "url": "v0.5.7/shebe-v0.5.7-linux-x86_64-musl.tar.gz",
+                    "sha256": "{}"
+                  }}
+                }}
+              }}]
+            }}"#,
+            schema_version, DIGEST,
+        )
+    }
+
+    #[test]
+    fn parses_version_1() {
+        let parsed = Manifest::parse(&manifest(1)).unwrap();
+        assert_eq!(parsed.releases.len(), 1);
+        assert!(!parsed.releases[0].prerelease);
+    }
+
+    #[test]
+    fn rejects_newer_schema() {
+        let err = Manifest::parse(&manifest(2)).unwrap_err();
+        assert!(err.contains("schema_version 2"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_unversioned_assets_format() {
+        let json = r#"{
+          "releases": [{
+            "version": "v0.5.7",
+            "assets": [{
+              "name": "shebe-v0.5.7-linux-x86_64-musl.tar.gz",
+              "url": "v0.5.7/shebe-v0.5.7-linux-x86_64-musl.tar.gz"
+            }]
+          }]
+        }"#;
+        let err = Manifest::parse(json).unwrap_err();
+        assert!(err.contains("no schema_version"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_bad_digest() {
+        let json = manifest(1).replace(DIGEST, "abc");
+        assert!(Manifest::parse(&json).is_err());
+    }
+
+    #[test]
+    fn converts_targets_to_assets() {
+        let parsed = Manifest::parse(&manifest(1)).unwrap();
+        let release = parsed.releases[0].to_release(
+            "https://mirror.example.com/shebe/releases.json",
+        );
+        assert_eq!(
+            release.assets,
+            [Asset {
+                name: "shebe-v0.5.7-linux-x86_64-musl.tar.gz".into(),
+                download_url: "https://mirror.example.com/shebe/\
+                               v0.5.7/shebe-v0.5.7-linux-x86_64-musl.tar.gz"
+                    .into(),
+                sha256: Some(DIGEST.into()),
+            }],
+        );
+    }
+
+    #[test]
+    fn min_extension_version_gates_releases() {
+        let mut release =
+            Manifest::parse(&manifest(1)).unwrap().releases.remove(0);
+        assert!(release.supports(&Version::new(0, 5, 0)));
+        assert!(!release.supports(&Version::new(0, 4, 9)));
+        release.min_extension_version = None;
+        assert!(release.supports(&Version::new(0, 1, 0)));
+    }
+
+    #[test]
+    fn target_round_trips_through_asset_name() {
+        let name = asset_name("v0.5.7", "darwin-aarch64");
+        let sidecar = format!("{}.sha256", name);
+        assert_eq!(
+            target_of("v0.5.7", &name),
+            Some("darwin-aarch64"),
+        );
+        assert_eq!(target_of("v0.5.8", &name), None);
+        assert_eq!(target_of("v0.5.7", "shebe-v0.5.7-.tar.gz"), None);
+        assert_eq!(target_of("v0.5.7", &sidecar), None);
+    }
+
+    #[test]
+    fn joins_urls_against_manifest_dir() {
+        let manifest =
+            "https://mirror.example.com/shebe/releases.json";
+        assert_eq!(
+            join_url(manifest, "v0.5.7/a.tar.gz"),
+            "https://mirror.example.com/shebe/v0.5.7/a.tar.gz",
+        );
+        assert_eq!(
+            join_url(manifest, "https://cdn.example.com/a.tar.gz"),
+            "https://cdn.example.com/a.tar.gz",
+        );
+    }
+}
@@ -0,0 +1,771 @@
+//! Minimal MCP client for talking to `shebe-mcp`.
+//!
+//! [`Client`] speaks JSON-RPC 2.0 over any [`Transport`]:
+//! it performs the `initialize` handshake, correlates
+//! responses with requests by id, collects server
+//! notifications and offers typed wrappers for shebe's
+//! tools in [`tools`]. Nothing here depends on how messages
+//! travel, so the same client runs inside the extension
+//! (over [`OneShot`]) and in native tests (over a child
+//! process's pipes).
+//!
+//! Requests are pipelined: the client never waits for a
+//! response before sending the next message. That lets a
+//! transport that cannot interleave reads and writes, like
+//! [`OneShot`], deliver a whole conversation at once.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use zed_extension_api::{
+    self as zed,
+    process::Command,
+    serde_json::{self, json, Value},
+};
+
+/// MCP revision spoken by `shebe-mcp`.
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// `initialize` parameters identifying this extension.
+pub fn initialize_params() -> Value {
+    json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "capabilities": {},
+        "clientInfo": {
+            "name": "zed-shebe",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+/// A channel carrying one JSON-RPC message at a time.
+pub trait Transport {
+    /// Queue or write one serialized message.
+    fn send(&mut self, message: &str) -> Result<(), String>;
+
+    /// The next message from the server, `None` once it has
+    /// closed its end.
+    fn receive(&mut self) -> Result<Option<String>, String>;
+}
+
+/// Why an MCP exchange failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum McpError {
+    /// The transport could not send or receive.
+    Transport(String),
+    /// The server closed the connection before answering.
+    Closed,
+    /// The server answered with a JSON-RPC error.
+    Protocol { code: i64, message: String },
+    /// The tool ran and reported failure (`isError`).
+    Tool(String),
+}
+
+impl fmt::Display for McpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(error) => f.write_str(error),
+            Self::Closed => {
+                f.write_str("shebe-mcp exited without answering")
+            }
+            Self::Protocol { code, message } => {
+                write!(f, "{} (JSON-RPC error {})", message, code)
+            }
+            Self::Tool(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<McpError> for String {
+    fn from(error: McpError) -> Self {
+        error.to_string()
+    }
+}
+
+/// A notification sent by the server, e.g. progress or a
+/// log message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub method: String,
+    pub params: Value,
+}
+
+/// An MCP client session over `T`.
+pub struct Client<T: Transport> {
+    transport: T,
+    next_id: u64,
+    initialize_id: u64,
+    /// Responses read while waiting for a different id.
+    responses: HashMap<u64, Value>,
+    notifications: Vec<Notification>,
+}
+
+impl<T: Transport> Client<T> {
+    /// Start a session: send `initialize` and the
+    /// `notifications/initialized` that follows it.
+    pub fn connect(transport: T) -> Result<Self, McpError> {
+        let mut client = Self {
+            transport,
+            next_id: 1,
+            initialize_id: 0,
+            responses: HashMap::new(),
+            notifications: Vec::new(),
+        };
+        client.initialize_id =
+            client.send_request("initialize", initialize_params())?;
+        client.notify("notifications/initialized", None)?;
+        Ok(client)
+    }
+
+    /// The `initialize` result: server name, version and
+    /// capabilities.
+    pub fn server_info(&mut self) -> Result<Value, McpError> {
+        self.response(self.initialize_id)
+    }
+
+    /// Send a request without waiting for its response.
+    /// Returns the id to pass to [`Client::response`].
+    pub fn send_request(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<u64, McpError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.write(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))?;
+        Ok(id)
+    }
+
+    /// Wait for the result of request `id`.
+    pub fn response(&mut self, id: u64) -> Result<Value, McpError> {
+        let response = loop {
+            if let Some(response) = self.responses.remove(&id) {
+                break response;
+            }
+            self.read_one()?;
+        };
+
+        if let Some(error) = response.get("error") {
+            return Err(McpError::Protocol {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"]
+                    .as_str()
+                    .unwrap_or("unknown error")
+                    .to_string(),
+            });
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    /// Send a request and wait for its result.
+    pub fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, McpError> {
+        let id = self.send_request(method, params)?;
+        self.response(id)
+    }
+
+    /// Send a notification.
+    pub fn notify(
+        &mut self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<(), McpError> {
+        let mut message = json!({ "jsonrpc": "2.0", "method": method });
+        if let Some(params) = params {
+            message["params"] = params;
+        }
+        self.write(&message)
+    }
+
+    /// Notifications received so far, oldest first.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    /// Names of the tools the server offers.
+    pub fn list_tools(&mut self) -> Result<Vec<String>, McpError> {
+        let result = self.request("tools/list", json!({}))?;
+        Ok(result["tools"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|tool| tool["name"].as_str())
+            .map(String::from)
+            .collect())
+    }
+
+    /// Call `tool` and join the text content of its result.
+    pub fn call_tool(
+        &mut self,
+        tool: &str,
+        arguments: Value,
+    ) -> Result<String, McpError> {
+        let result = self.request(
+            "tools/call",
+            json!({ "name": tool, "arguments": arguments }),
+        )?;
+        let text = result["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if result["isError"] == json!(true) {
+            return Err(McpError::Tool(text));
+        }
+        Ok(text)
+    }
+
+    /// Call one of shebe's tools with typed arguments.
+    pub fn call<C: tools::Tool>(
+        &mut self,
+        call: &C,
+    ) -> Result<String, McpError> {
+        let arguments = serde_json::to_value(call)
+            .map_err(|e| McpError::Transport(e.to_string()))?;
+        self.call_tool(C::NAME, arguments)
+    }
+
+    fn write(&mut self, message: &Value) -> Result<(), McpError> {
+        self.transport
+            .send(&message.to_string())
+            .map_err(McpError::Transport)
+    }
+
+    /// Read and file away one message from the server.
+    fn read_one(&mut self) -> Result<(), McpError> {
+        let line = self
+            .transport
+            .receive()
+            .map_err(McpError::Transport)?
+            .ok_or(McpError::Closed)?;
+        // Servers may log to stdout; anything that is not a
+        // JSON-RPC message is skipped.
+        let Ok(message) = serde_json::from_str::<Value>(&line)
+        else {
+            return Ok(());
+        };
+
+        let id = message.get("id").cloned();
+        match (id, message["method"].as_str()) {
+            (Some(id), None) => {
+                if let Some(id) = id.as_u64() {
+                    self.responses.insert(id, message);
+                }
+            }
+            (None, Some(method)) => {
+                self.notifications.push(Notification {
+                    method: method.to_string(),
+                    params: message["params"].clone(),
+                });
+            }
+            // A request from the server. Only `ping` is
+            // answered; the client offers no capabilities.
+            (Some(id), Some(method)) => {
+                let reply = if method == "ping" {
+                    json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+                } else {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": -32601,
+                            "message": "method not found",
+                        },
+                    })
+                };
+                // A transport that can no longer write, such
+                // as a finished one-shot, drops the reply.
+                self.write(&reply).ok();
+            }
+            (None, None) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Run a fresh `shebe-mcp` with everything sent so far on
+/// its stdin, then replay its stdout.
+///
+/// `zed::process` cannot write to a running child, so the
+/// messages are buffered until the first
+/// [`Transport::receive`] and piped in by
+/// [`ONE_SHOT_SCRIPT`]. The server answers and exits at end
+/// of input; sends after that fail. `server` must be
+/// `shebe-mcp` itself: tools that need the worktree get its
+/// path as an argument, so the working directory does not
+/// matter.
+pub struct OneShot {
+    server: zed::Command,
+    input: Vec<String>,
+    output: Option<VecDeque<String>>,
+    /// Exit status once the server has run, `None` if it
+    /// was killed by a signal.
+    status: Option<i32>,
+    stderr: String,
+}
+
+/// Pipes `$SHEBE_MCP_INPUT` into `"$0" "$@"` for a binary
+/// named `shebe-mcp` and refuses anything else. The
+/// `process:exec` capability in `extension.toml` grants
+/// exactly this script, so slash commands cannot run
+/// arbitrary commands.
+pub const ONE_SHOT_SCRIPT: &str = "case \"$0\" in shebe-mcp|*/shebe-mcp) ;; \
+                                   *) echo \"not a shebe-mcp binary\" >&2\n\
+                                   exit 126 ;; esac\n\
+                                   [ -z \"$SHEBE_DATA_DIR\" ] || \
+                                   mkdir -p \"$SHEBE_DATA_DIR\"\n\
+                                   printf '%s\\n' \"$SHEBE_MCP_INPUT\" \
+                                   | \"$0\" \"$@\"";
+
+impl OneShot {
+    pub fn new(server: zed::Command) -> Self {
+        Self {
+            server,
+            input: Vec::new(),
+            output: None,
+            status: None,
+            stderr: String::new(),
+        }
+    }
+
+    fn run(&mut self) -> Result<VecDeque<String>, String> {
+        let output = Command::new("/bin/sh")
+            .args(["-c", ONE_SHOT_SCRIPT])
+            .arg(&self.server.command)
+            .args(self.server.args.iter())
+            .envs(self.server.env.iter().cloned())
+            .env("SHEBE_MCP_INPUT", self.input.join("\n"))
+            .output()
+            .map_err(|e| format!("failed to start shebe-mcp: {}", e))?;
+        self.status = output.status;
+        self.stderr =
+            String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+}
+
+impl Transport for OneShot {
+    fn send(&mut self, message: &str) -> Result<(), String> {
+        if self.output.is_some() {
+            return Err("one-shot shebe-mcp has already exited".into());
+        }
+        self.input.push(message.to_string());
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Option<String>, String> {
+        if self.output.is_none() {
+            self.output = Some(self.run()?);
+        }
+        match self.output.as_mut().and_then(VecDeque::pop_front) {
+            Some(line) => Ok(Some(line)),
+            None => exited(self.status, &self.stderr),
+        }
+    }
+}
+
+/// What [`OneShot`] reports once the server's output is
+/// used up: a clean exit is an ordinary end of stream, a
+/// failed one an error carrying its stderr. Servers log to
+/// stderr, so its contents alone mean nothing.
+fn exited(
+    status: Option<i32>,
+    stderr: &str,
+) -> Result<Option<String>, String> {
+    let reason = match status {
+        Some(0) => return Ok(None),
+        Some(code) => format!("shebe-mcp exited with status {}", code),
+        None => "shebe-mcp was killed by a signal".to_string(),
+    };
+    Err(match stderr {
+        "" => reason,
+        stderr => format!("{}: {}", reason, stderr),
+    })
+}
+
+/// Call `tool` on a fresh `server`.
+pub fn call<C: tools::Tool>(
+    server: &zed::Command,
+    tool: &C,
+) -> Result<String, McpError> {
+    Client::connect(OneShot::new(server.clone()))?.call(tool)
+}
+
+/// Typed arguments for the tools `shebe-mcp` documents.
+///
+/// Each struct serializes to the tool's `arguments` object;
+/// optional fields left as `None` fall back to the server's
+/// defaults.
+pub mod tools {
+    use serde::Serialize;
+
+    /// A `shebe-mcp` tool and its arguments.
+    pub trait Tool: Serialize {
+        /// Tool name in `tools/call`.
+        const NAME: &'static str;
+    }
+
+    macro_rules! tools {
+        ($(
+            $(#[$doc:meta])*
+            $name:ident => $tool:literal {
+                $($field:ident: $ty:ty),* $(,)?
+            }
+        )*) => {$(
+            $(#[$doc])*
+            #[derive(Debug, Clone, Default, PartialEq, Serialize)]
+            pub struct $name {
+                $(
+                    #[serde(skip_serializing_if = "is_unset")]
+                    pub $field: $ty,
+                )*
+            }
+
+            impl Tool for $name {
+                const NAME: &'static str = $tool;
+            }
+        )*};
+    }
+
+    /// Whether an argument is left to the server default.
+    trait Unset {
+        fn unset(&self) -> bool;
+    }
+
+    impl Unset for String {
+        fn unset(&self) -> bool {
+            false
+        }
+    }
+
+    impl<T> Unset for Option<T> {
+        fn unset(&self) -> bool {
+            self.is_none()
+        }
+    }
+
+    fn is_unset<T: Unset>(value: &T) -> bool {
+        value.unset()
+    }
+
+    tools! {
+        /// BM25 full-text search of a session.
+        SearchCode => "search_code" {
+            query: String,
+            session: String,
+            k: Option<u32>,
+        }
+        /// Index a directory into a session.
+        IndexRepository => "index_repository" {
+            path: String,
+            session: String,
+            force: Option<bool>,
+        }
+        /// References to a symbol, ranked by confidence.
+        FindReferences => "find_references" {
+            symbol: String,
+            session: String,
+            max_results: Option<u32>,
+        }
+        /// All indexed sessions.
+        ListSessions => "list_sessions" {}
+        /// Metadata and statistics of a session.
+        GetSessionInfo => "get_session_info" {
+            session: String,
+        }
+        /// Server version and capabilities.
+        GetServerInfo => "get_server_info" {}
+        /// The server's effective configuration.
+        ShowShebeConfig => "show_shebe_config" {}
+        /// Contents of an indexed file.
+        ReadFile => "read_file" {
+            session: String,
+            file_path: String,
+        }
+        /// Files matching a glob or regex.
+        FindFile => "find_file" {
+            session: String,
+            pattern: String,
+        }
+        /// Contents of a directory in a session.
+        ListDir => "list_dir" {
+            session: String,
+            path: Option<String>,
+        }
+        /// Context around an indexed chunk.
+        PreviewChunk => "preview_chunk" {
+            session: String,
+            file_path: String,
+            chunk_index: Option<u32>,
+        }
+        /// Re-index a session from its stored path.
+        ReindexSession => "reindex_session" {
+            session: String,
+        }
+        /// Remove a session and its index.
+        DeleteSession => "delete_session" {
+            session: String,
+        }
+        /// Upgrade a session to the current schema.
+        UpgradeSession => "upgrade_session" {
+            session: String,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tools::*;
+    use super::*;
+
+    /// Records what the client sends and replays canned
+    /// server output.
+    #[derive(Default)]
+    struct Script {
+        sent: Vec<Value>,
+        replies: VecDeque<String>,
+    }
+
+    impl Script {
+        fn new(replies: &[Value]) -> Self {
+            Self {
+                sent: Vec::new(),
+                replies: replies.iter().map(Value::to_string).collect(),
+            }
+        }
+    }
+
+    impl Transport for &mut Script {
+        fn send(&mut self, message: &str) -> Result<(), String> {
+            self.sent.push(serde_json::from_str(message).unwrap());
+            Ok(())
+        }
+
+        fn receive(&mut self) -> Result<Option<String>, String> {
+            Ok(self.replies.pop_front())
+        }
+    }
+
+    fn init() -> Value {
+        response(1, json!({ "serverInfo": { "name": "shebe-mcp" } }))
+    }
+
+    fn response(id: u64, result: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "result": result })
+    }
+
+    fn text(text: &str, is_error: bool) -> Value {
+        json!({
+            "isError": is_error,
+            "content": [{ "type": "text", "text": text }],
+        })
+    }
+
+    #[test]
+    fn handshake_precedes_pipelined_call() {
+        let mut script = Script::new(&[
+            init(),
+            response(2, text("ok", false)),
+        ]);
+        let text = Client::connect(&mut script)
+            .unwrap()
+            .call(&SearchCode {
+                query: "parse".into(),
+                session: "app".into(),
+                k: None,
+            })
+            .unwrap();
+
+        assert_eq!(text, "ok");
+        let methods: Vec<_> = script
+            .sent
+            .iter()
+            .map(|m| m["method"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            methods,
+            ["initialize", "notifications/initialized", "tools/call"],
+        );
+        assert_eq!(
+            script.sent[2]["params"],
+            json!({
+                "name": "search_code",
+                "arguments": { "query": "parse", "session": "app" },
+            }),
+        );
+    }
+
+    #[test]
+    fn correlates_out_of_order_responses() {
+        let mut script = Script::new(&[
+            response(3, json!("third")),
+            response(2, json!("second")),
+            init(),
+        ]);
+        script.replies.push_front("shebe-mcp: not json".into());
+        let mut client = Client::connect(&mut script).unwrap();
+        let second = client.send_request("a", json!({})).unwrap();
+        let third = client.send_request("b", json!({})).unwrap();
+
+        assert_eq!(client.response(second).unwrap(), "second");
+        assert_eq!(client.response(third).unwrap(), "third");
+        assert_eq!(
+            client.server_info().unwrap()["serverInfo"]["name"],
+            "shebe-mcp",
+        );
+    }
+
+    #[test]
+    fn collects_notifications_and_answers_ping() {
+        let mut script = Script::new(&[
+            init(),
+            json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": { "progress": 50 },
+            }),
+            json!({ "jsonrpc": "2.0", "id": "srv-1", "method": "ping" }),
+            json!({ "jsonrpc": "2.0", "id": 7, "method": "ping" }),
+            response(2, json!({ "tools": [{ "name": "search_code" }] })),
+        ]);
+        let mut client = Client::connect(&mut script).unwrap();
+
+        assert_eq!(client.list_tools().unwrap(), ["search_code"]);
+        assert_eq!(
+            client.take_notifications(),
+            [Notification {
+                method: "notifications/progress".into(),
+                params: json!({ "progress": 50 }),
+            }],
+        );
+        assert!(client.take_notifications().is_empty());
+        drop(client);
+        assert_eq!(
+            script.sent.last().unwrap(),
+            &json!({ "jsonrpc": "2.0", "id": 7, "result": {} }),
+        );
+    }
+
+    #[test]
+    fn reports_tool_protocol_and_closed_errors() {
+        let mut script = Script::new(&[
+            init(),
+            response(2, text("no session", true)),
+            json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "error": { "code": -32602, "message": "bad params" },
+            }),
+        ]);
+        let mut client = Client::connect(&mut script).unwrap();
+
+        assert_eq!(
+            client.call(&ListSessions {}),
+            Err(McpError::Tool("no session".into())),
+        );
+        assert_eq!(
+            client.call(&ListSessions {}),
+            Err(McpError::Protocol {
+                code: -32602,
+                message: "bad params".into(),
+            }),
+        );
+        assert_eq!(client.call(&ListSessions {}), Err(McpError::Closed));
+    }
+
+    #[test]
+    fn one_shot_script_is_granted() {
+        let script = serde_json::to_string(ONE_SHOT_SCRIPT).unwrap();
+        let manifest = include_str!("../extension.toml");
+        assert!(
+            manifest.contains(&format!("args = [\"-c\", {}, \"**\"]", script)),
+            "extension.toml must grant /bin/sh -c {}",
+            script,
+        );
+        assert!(!manifest.contains("args = [\"-c\", \"**\"]"));
+    }
+
+    #[test]
+    fn one_shot_script_only_runs_shebe_mcp() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["shebe-mcp", "other"] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "#!/bin/sh\necho \"$@\"\ncat\n")
+                .unwrap();
+            std::process::Command::new("chmod")
+                .arg("+x")
+                .arg(&path)
+                .status()
+                .unwrap();
+        }
+        let run = |name: &str| {
+            std::process::Command::new("/bin/sh")
+                .args(["-c", ONE_SHOT_SCRIPT])
+                .arg(dir.path().join(name))
+                .args(["--flag", "two words"])
+                .env("SHEBE_MCP_INPUT", "{}\n{}")
+                .output()
+                .unwrap()
+        };
+        let ok = run("shebe-mcp");
+        assert_eq!(
+            String::from_utf8_lossy(&ok.stdout),
+            "--flag two words\n{}\n{}\n",
+        );
+        let refused = run("other");
+        assert_eq!(refused.status.code(), Some(126));
+        assert!(refused.stdout.is_empty());
+    }
+
+    #[test]
+    fn one_shot_end_depends_on_exit_status() {
+        assert_eq!(exited(Some(0), "INFO started"), Ok(None));
+        assert_eq!(
+            exited(Some(2), "no such session"),
+            Err("shebe-mcp exited with status 2: no such session".into()),
+        );
+        assert_eq!(
+            exited(Some(126), ""),
+            Err("shebe-mcp exited with status 126".into()),
+        );
+        assert_eq!(
+            exited(None, ""),
+            Err("shebe-mcp was killed by a signal".into()),
+        );
+    }
+
+    #[test]
+    fn tool_arguments_omit_unset_options() {
+        let call = IndexRepository {
+            path: "/src/app".into(),
+            session: "app".into(),
+            force: Some(true),
+        };
+        assert_eq!(IndexRepository::NAME, "index_repository");
+        assert_eq!(
+            serde_json::to_value(&call).unwrap(),
+            json!({ "path": "/src/app", "session": "app", "force": true }),
+        );
+        assert_eq!(
+            serde_json::to_value(ListDir {
+                session: "app".into(),
+                path: None,
+            })
+            .unwrap(),
+            json!({ "session": "app" }),
+        );
+    }
+}
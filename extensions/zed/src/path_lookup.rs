@@ -0,0 +1,77 @@
+//! Discovery of a `shebe-mcp` already installed on `PATH`,
+//! and the host probes that go with it.
+//!
+//! Users who installed shebe via Homebrew already have a
+//! `shebe-mcp` on their `PATH`. Reusing it avoids keeping a
+//! second copy in the extension work dir.
+
+use semver::Version;
+use zed_extension_api::process::Command;
+
+use crate::health;
+use crate::release::VersionSpec;
+
+/// Oldest `shebe-mcp` release the extension will run from
+/// `PATH`. Anything older is ignored and a managed copy is
+/// downloaded instead.
+pub const MIN_VERSION: Version = Version::new(0, 5, 0);
+
+/// Resolve `shebe-mcp` with `command -v` in a non-login
+/// `/bin/sh`, i.e. on the `PATH` Zed itself was started
+/// with.
+///
+/// `Worktree::which` would see the user's shell environment,
+/// but `context_server_command` only gets a `Project`, which
+/// exposes no worktree. `PATH` entries added only by shell
+/// profiles Zed did not load are missed; `binary_path`
+/// covers those.
+fn which() -> Option<String> {
+    let output = Command::new("/bin/sh")
+        .args(["-c", "command -v shebe-mcp"])
+        .output()
+        .ok()?;
+    if output.status != Some(0) {
+        return None;
+    }
+
+    let path = String::from_utf8(output.stdout).ok()?;
+    let path = path.trim();
+    if path.is_empty() {
+        None
+    } else {
+        Some(path.to_string())
+    }
+}
+
+/// The version `binary --version` reports, if it runs.
+pub fn binary_version(binary: &str) -> Option<Version> {
+    health::version(binary).ok()
+}
+
+/// Whether the host's C library is glibc.
+///
+/// `getconf GNU_LIBC_VERSION` only succeeds on glibc systems;
+/// musl (Alpine) and macOS both reject the variable.
+pub fn host_is_glibc() -> bool {
+    Command::new("getconf")
+        .arg("GNU_LIBC_VERSION")
+        .output()
+        .is_ok_and(|output| {
+            output.status == Some(0)
+                && String::from_utf8_lossy(&output.stdout)
+                    .starts_with("glibc")
+        })
+}
+
+/// Return the path of a `shebe-mcp` on `PATH` whose
+/// `--version` is at least [`MIN_VERSION`] and satisfies the
+/// configured version pin.
+pub fn find(pin: &VersionSpec) -> Option<String> {
+    let path = which()?;
+    let version = binary_version(&path)?;
+    if version >= MIN_VERSION && pin.matches(&version) {
+        Some(path)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,609 @@
+//! Platform detection and release asset resolution.
+//!
+//! Pure, host-independent logic shared by the extension and
+//! the integration tests: no `zed_extension_api` types appear
+//! here, so everything is unit-testable on a plain native
+//! target. The extension converts `zed::current_platform()`,
+//! GitHub releases and manifest releases into the types
+//! below.
+//!
+//! Asset naming convention:
+//!
+//! ```text
+//! shebe-{version}-{os}-{arch}{suffix}.tar.gz
+//! shebe-{version}-{os}-{arch}{suffix}.tar.gz.sha256
+//! ```
+//!
+//! where `suffix` is `-musl` for the static Linux build and
+//! empty for the glibc build and macOS. Assets from a
+//! release manifest carry their digest instead of a
+//! `.sha256` sidecar.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Operating system of the host running Zed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    Darwin,
+    Linux,
+    Windows,
+}
+
+impl Os {
+    /// The `{os}` component of asset names.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Darwin => "darwin",
+            Self::Linux => "linux",
+            Self::Windows => "windows",
+        }
+    }
+}
+
+/// CPU architecture of the host running Zed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    Aarch64,
+    X86_64,
+    X86,
+}
+
+impl Arch {
+    /// The `{arch}` component of asset names.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Aarch64 => "aarch64",
+            Self::X86_64 => "x86_64",
+            Self::X86 => "x86",
+        }
+    }
+}
+
+/// C library flavour of the Linux build to install.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Libc {
+    /// glibc build if the host runs glibc and the release
+    /// has one, the static musl build otherwise.
+    #[default]
+    Auto,
+    /// Statically linked `-musl` build.
+    Musl,
+    /// glibc build, the one the Homebrew formula installs.
+    Gnu,
+}
+
+/// Facts about the host that decide which asset to install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    pub os: Os,
+    pub arch: Arch,
+    /// Whether the host's C library is glibc. Only consulted
+    /// on Linux with [`Libc::Auto`].
+    pub glibc: bool,
+}
+
+/// A downloadable file attached to a release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Asset {
+    pub name: String,
+    pub download_url: String,
+    /// Expected SHA256, when the release source publishes it
+    /// inline rather than as a `.sha256` sidecar.
+    pub sha256: Option<String>,
+}
+
+/// A release as seen by the resolver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    /// Release tag, e.g. `v0.5.7`.
+    pub version: String,
+    pub assets: Vec<Asset>,
+}
+
+/// The archive to install and the checksum that covers it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedAsset<'a> {
+    pub archive: &'a Asset,
+    pub checksum: Checksum<'a>,
+}
+
+/// Where the expected digest of an archive comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum<'a> {
+    /// A `.sha256` sidecar asset to download.
+    Sidecar(&'a Asset),
+    /// A digest published alongside the archive.
+    Sha256(&'a str),
+}
+
+/// Why no asset could be chosen for a platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlatformError {
+    /// shebe is not built for this operating system.
+    UnsupportedOs(Os),
+    /// shebe is not built for this architecture.
+    UnsupportedArch(Arch),
+    /// Linux ARM builds are missing from this release.
+    LinuxArmUnavailable {
+        version: String,
+        available: Vec<String>,
+    },
+    /// None of the candidate archive names are published.
+    MissingAsset {
+        candidates: Vec<String>,
+        available: Vec<String>,
+    },
+    /// The archive has neither an inline digest nor a
+    /// `.sha256` sidecar.
+    MissingChecksum { name: String },
+}
+
+impl fmt::Display for PlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOs(Os::Windows) => {
+                write!(f, "shebe does not support Windows")
+            }
+            Self::UnsupportedOs(os) => write!(
+                f,
+                "shebe does not support {}",
+                os.as_str(),
+            ),
+            Self::UnsupportedArch(Arch::X86) => {
+                write!(f, "shebe does not support 32-bit x86")
+            }
+            Self::UnsupportedArch(arch) => write!(
+                f,
+                "shebe does not support {}",
+                arch.as_str(),
+            ),
+            Self::LinuxArmUnavailable { version, available } => {
+                write!(
+                    f,
+                    "shebe does not support Linux ARM in release \
+                     {}; available assets: {}",
+                    version,
+                    available.join(", "),
+                )
+            }
+            Self::MissingAsset {
+                candidates,
+                available,
+            } => write!(
+                f,
+                "no release asset matching '{}'; available \
+                 assets: {}",
+                candidates.join("' or '"),
+                available.join(", "),
+            ),
+            Self::MissingChecksum { name } => write!(
+                f,
+                "no checksum asset matching '{}.sha256'; \
+                 refusing to install an unverified binary",
+                name,
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlatformError {}
+
+/// Format a release archive name.
+pub fn asset_name(
+    version: &str,
+    os: Os,
+    arch: Arch,
+    suffix: &str,
+) -> String {
+    format!(
+        "shebe-{}-{}-{}{}.tar.gz",
+        version,
+        os.as_str(),
+        arch.as_str(),
+        suffix,
+    )
+}
+
+/// Archive names to look for, most preferred first.
+pub fn candidate_names(
+    version: &str,
+    platform: Platform,
+    libc: Libc,
+) -> Result<Vec<String>, PlatformError> {
+    match platform.os {
+        Os::Darwin | Os::Linux => {}
+        os => return Err(PlatformError::UnsupportedOs(os)),
+    }
+    match platform.arch {
+        Arch::Aarch64 | Arch::X86_64 => {}
+        arch => return Err(PlatformError::UnsupportedArch(arch)),
+    }
+
+    // Linux releases ship a static musl build and a glibc
+    // build; macOS has a single build per arch.
+    let suffixes: &[&str] = match (platform.os, libc) {
+        (Os::Linux, Libc::Musl) => &["-musl"],
+        (Os::Linux, Libc::Gnu) => &[""],
+        (Os::Linux, Libc::Auto) if platform.glibc => {
+            &["", "-musl"]
+        }
+        (Os::Linux, Libc::Auto) => &["-musl"],
+        _ => &[""],
+    };
+
+    Ok(suffixes
+        .iter()
+        .map(|suffix| {
+            asset_name(version, platform.os, platform.arch, suffix)
+        })
+        .collect())
+}
+
+/// Choose the archive and checksum to install from `release`.
+pub fn resolve(
+    release: &Release,
+    platform: Platform,
+    libc: Libc,
+) -> Result<ResolvedAsset<'_>, PlatformError> {
+    let candidates =
+        candidate_names(&release.version, platform, libc)?;
+    let find = |name: &str| {
+        release.assets.iter().find(|a| a.name == name)
+    };
+
+    let Some(archive) =
+        candidates.iter().find_map(|name| find(name))
+    else {
+        let available: Vec<String> = release
+            .assets
+            .iter()
+            .map(|a| a.name.clone())
+            .filter(|name| name.ends_with(".tar.gz"))
+            .collect();
+        // Linux ARM builds are newer than the other targets
+        // and missing from older releases.
+        return Err(
+            if platform.os == Os::Linux
+                && platform.arch == Arch::Aarch64
+            {
+                PlatformError::LinuxArmUnavailable {
+                    version: release.version.clone(),
+                    available,
+                }
+            } else {
+                PlatformError::MissingAsset {
+                    candidates,
+                    available,
+                }
+            },
+        );
+    };
+
+    let checksum = match &archive.sha256 {
+        Some(digest) => Checksum::Sha256(digest),
+        None => find(&format!("{}.sha256", archive.name))
+            .map(Checksum::Sidecar)
+            .ok_or_else(|| PlatformError::MissingChecksum {
+                name: archive.name.clone(),
+            })?,
+    };
+
+    Ok(ResolvedAsset { archive, checksum })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSION: &str = "v0.5.7";
+
+    fn platform(os: Os, arch: Arch, glibc: bool) -> Platform {
+        Platform { os, arch, glibc }
+    }
+
+    /// A release publishing `names` plus a `.sha256` sidecar
+    /// for each.
+    fn release(names: &[&str]) -> Release {
+        let assets = names
+            .iter()
+            .flat_map(|name| {
+                [name.to_string(), format!("{}.sha256", name)]
+            })
+            .map(|name| Asset {
+                download_url: format!("https://example.test/{}", name),
+                name,
+                sha256: None,
+            })
+            .collect();
+        Release {
+            version: VERSION.into(),
+            assets,
+        }
+    }
+
+    fn full_release() -> Release {
+        release(&[
+            "shebe-v0.5.7-darwin-aarch64.tar.gz",
+            "shebe-v0.5.7-darwin-x86_64.tar.gz",
+            "shebe-v0.5.7-linux-x86_64.tar.gz",
+            "shebe-v0.5.7-linux-x86_64-musl.tar.gz",
+            "shebe-v0.5.7-linux-aarch64-musl.tar.gz",
+        ])
+    }
+
+    fn resolved_name(
+        release: &Release,
+        platform: Platform,
+        libc: Libc,
+    ) -> Result<String, PlatformError> {
+        resolve(release, platform, libc)
+            .map(|r| r.archive.name.clone())
+    }
+
+    #[test]
+    fn asset_name_format() {
+        assert_eq!(
+            asset_name(VERSION, Os::Linux, Arch::X86_64, "-musl"),
+            "shebe-v0.5.7-linux-x86_64-musl.tar.gz",
+        );
+        assert_eq!(
+            asset_name(VERSION, Os::Darwin, Arch::Aarch64, ""),
+            "shebe-v0.5.7-darwin-aarch64.tar.gz",
+        );
+    }
+
+    #[test]
+    fn darwin_ignores_libc() {
+        for libc in [Libc::Auto, Libc::Musl, Libc::Gnu] {
+            for arch in [Arch::Aarch64, Arch::X86_64] {
+                let names = candidate_names(
+                    VERSION,
+                    platform(Os::Darwin, arch, false),
+                    libc,
+                )
+                .unwrap();
+                assert_eq!(
+                    names,
+                    [asset_name(VERSION, Os::Darwin, arch, "")],
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn linux_candidates_follow_libc() {
+        let cases = [
+            (Libc::Musl, false, vec!["-musl"]),
+            (Libc::Musl, true, vec!["-musl"]),
+            (Libc::Gnu, false, vec![""]),
+            (Libc::Gnu, true, vec![""]),
+            (Libc::Auto, false, vec!["-musl"]),
+            (Libc::Auto, true, vec!["", "-musl"]),
+        ];
+        for (libc, glibc, suffixes) in cases {
+            let names = candidate_names(
+                VERSION,
+                platform(Os::Linux, Arch::X86_64, glibc),
+                libc,
+            )
+            .unwrap();
+            let expected: Vec<String> = suffixes
+                .iter()
+                .map(|s| {
+                    asset_name(VERSION, Os::Linux, Arch::X86_64, s)
+                })
+                .collect();
+            assert_eq!(names, expected, "{:?} glibc={}", libc, glibc);
+        }
+    }
+
+    #[test]
+    fn windows_is_unsupported() {
+        for arch in [Arch::Aarch64, Arch::X86_64, Arch::X86] {
+            assert_eq!(
+                candidate_names(
+                    VERSION,
+                    platform(Os::Windows, arch, false),
+                    Libc::Auto,
+                ),
+                Err(PlatformError::UnsupportedOs(Os::Windows)),
+            );
+        }
+    }
+
+    #[test]
+    fn x86_is_unsupported() {
+        for os in [Os::Darwin, Os::Linux] {
+            assert_eq!(
+                candidate_names(
+                    VERSION,
+                    platform(os, Arch::X86, false),
+                    Libc::Auto,
+                ),
+                Err(PlatformError::UnsupportedArch(Arch::X86)),
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_every_supported_platform() {
+        let release = full_release();
+        let cases = [
+            (Os::Darwin, Arch::Aarch64, "darwin-aarch64"),
+            (Os::Darwin, Arch::X86_64, "darwin-x86_64"),
+            (Os::Linux, Arch::X86_64, "linux-x86_64-musl"),
+            (Os::Linux, Arch::Aarch64, "linux-aarch64-musl"),
+        ];
+        for (os, arch, target) in cases {
+            let resolved = resolve(
+                &release,
+                platform(os, arch, false),
+                Libc::Auto,
+            )
+            .unwrap();
+            let name = format!("shebe-v0.5.7-{}.tar.gz", target);
+            assert_eq!(resolved.archive.name, name);
+            let Checksum::Sidecar(sidecar) = resolved.checksum
+            else {
+                panic!("expected a sidecar for {}", name);
+            };
+            assert_eq!(sidecar.name, format!("{}.sha256", name));
+        }
+    }
+
+    #[test]
+    fn auto_prefers_glibc_on_glibc_hosts() {
+        assert_eq!(
+            resolved_name(
+                &full_release(),
+                platform(Os::Linux, Arch::X86_64, true),
+                Libc::Auto,
+            ),
+            Ok("shebe-v0.5.7-linux-x86_64.tar.gz".into()),
+        );
+    }
+
+    #[test]
+    fn auto_falls_back_to_musl_without_glibc_build() {
+        assert_eq!(
+            resolved_name(
+                &full_release(),
+                platform(Os::Linux, Arch::Aarch64, true),
+                Libc::Auto,
+            ),
+            Ok("shebe-v0.5.7-linux-aarch64-musl.tar.gz".into()),
+        );
+    }
+
+    #[test]
+    fn explicit_gnu_does_not_fall_back() {
+        let err = resolve(
+            &full_release(),
+            platform(Os::Linux, Arch::Aarch64, true),
+            Libc::Gnu,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            PlatformError::LinuxArmUnavailable { .. }
+        ));
+    }
+
+    #[test]
+    fn linux_arm_missing_names_available_assets() {
+        let release = release(&[
+            "shebe-v0.5.7-darwin-aarch64.tar.gz",
+            "shebe-v0.5.7-linux-x86_64-musl.tar.gz",
+        ]);
+        let err = resolve(
+            &release,
+            platform(Os::Linux, Arch::Aarch64, false),
+            Libc::Auto,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            PlatformError::LinuxArmUnavailable {
+                version: VERSION.into(),
+                available: vec![
+                    "shebe-v0.5.7-darwin-aarch64.tar.gz".into(),
+                    "shebe-v0.5.7-linux-x86_64-musl.tar.gz".into(),
+                ],
+            },
+        );
+        assert!(err.to_string().contains("Linux ARM"));
+    }
+
+    #[test]
+    fn missing_asset_lists_candidates() {
+        let release = release(&["shebe-v0.5.7-darwin-aarch64.tar.gz"]);
+        let err = resolve(
+            &release,
+            platform(Os::Linux, Arch::X86_64, true),
+            Libc::Auto,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            PlatformError::MissingAsset {
+                candidates: vec![
+                    "shebe-v0.5.7-linux-x86_64.tar.gz".into(),
+                    "shebe-v0.5.7-linux-x86_64-musl.tar.gz".into(),
+                ],
+                available: vec![
+                    "shebe-v0.5.7-darwin-aarch64.tar.gz".into(),
+                ],
+            },
+        );
+    }
+
+    #[test]
+    fn missing_checksum_is_an_error() {
+        let mut release =
+            release(&["shebe-v0.5.7-darwin-x86_64.tar.gz"]);
+        release.assets.retain(|a| !a.name.ends_with(".sha256"));
+        assert_eq!(
+            resolve(
+                &release,
+                platform(Os::Darwin, Arch::X86_64, false),
+                Libc::Auto,
+            ),
+            Err(PlatformError::MissingChecksum {
+                name: "shebe-v0.5.7-darwin-x86_64.tar.gz".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn checksum_follows_chosen_archive() {
+        // glibc archive lacks a sidecar; auto must not pair
+        // it with the musl checksum.
+        let mut release = full_release();
+        release.assets.retain(|a| {
+            a.name != "shebe-v0.5.7-linux-x86_64.tar.gz.sha256"
+        });
+        assert_eq!(
+            resolve(
+                &release,
+                platform(Os::Linux, Arch::X86_64, true),
+                Libc::Auto,
+            ),
+            Err(PlatformError::MissingChecksum {
+                name: "shebe-v0.5.7-linux-x86_64.tar.gz".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn inline_digest_needs_no_sidecar() {
+        let name = "shebe-v0.5.7-darwin-aarch64.tar.gz";
+        let release = Release {
+            version: VERSION.into(),
+            assets: vec![Asset {
+                name: name.into(),
+                download_url: format!("https://example.test/{}", name),
+                sha256: Some("ab".repeat(32)),
+            }],
+        };
+        let resolved = resolve(
+            &release,
+            platform(Os::Darwin, Arch::Aarch64, false),
+            Libc::Auto,
+        )
+        .unwrap();
+        let digest = "ab".repeat(32);
+        assert_eq!(resolved.checksum, Checksum::Sha256(&digest));
+    }
+}
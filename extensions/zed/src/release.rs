@@ -0,0 +1,624 @@
+//! Release resolution for the `version` setting.
+//!
+//! The default follows the latest GitHub release. A pin can
+//! name an exact tag (`0.5.7`, `v0.5.7`) or a semver range
+//! (`~0.5`, `>=0.5, <0.7`); ranges are resolved against the
+//! release list and the highest matching version wins.
+//!
+//! The `channel` setting decides whether pre-releases are
+//! considered at all.
+//!
+//! `previous` rolls back to the install that was active
+//! before the current one; it is resolved from the state
+//! file, never against GitHub.
+//!
+//! Releases come from the GitHub repository or release
+//! manifest named by `release_source`; see
+//! [`crate::source`] and [`crate::manifest`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use zed_extension_api::{
+    self as zed,
+    http_client::{HttpMethod, HttpRequest, RedirectPolicy},
+    serde_json,
+};
+
+use crate::manifest::{Manifest, EXTENSION_VERSION};
+use crate::platform::{Asset, Libc, Release};
+use crate::source::ReleaseSource;
+
+const GITHUB_API: &str = "https://api.github.com";
+
+/// Which release of `shebe-mcp` to install.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum VersionSpec {
+    /// Whatever GitHub reports as the latest release.
+    #[default]
+    Latest,
+    /// A single release, e.g. `0.5.7`.
+    Exact(Version),
+    /// The highest release matching a semver range.
+    Range(VersionReq),
+    /// The install that was active before the current one.
+    Previous,
+}
+
+impl FromStr for VersionSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("latest") {
+            return Ok(Self::Latest);
+        }
+        if s.eq_ignore_ascii_case("previous") {
+            return Ok(Self::Previous);
+        }
+        if let Ok(version) =
+            Version::parse(s.strip_prefix('v').unwrap_or(s))
+        {
+            return Ok(Self::Exact(version));
+        }
+        VersionReq::parse(s).map(Self::Range).map_err(|e| {
+            format!(
+                "invalid version '{}': expected \"latest\", \
+                 \"previous\", a tag such as \"v0.5.7\" or a \
+                 semver range such as \"~0.5\" ({})",
+                s, e,
+            )
+        })
+    }
+}
+
+impl TryFrom<String> for VersionSpec {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for VersionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Latest => f.write_str("latest"),
+            Self::Exact(version) => write!(f, "v{}", version),
+            Self::Range(req) => write!(f, "{}", req),
+            Self::Previous => f.write_str("previous"),
+        }
+    }
+}
+
+impl VersionSpec {
+    /// Whether an already installed `version` satisfies this
+    /// pin. `Latest` accepts any version; `Previous` names an
+    /// install rather than a version and matches none.
+    pub fn matches(&self, version: &Version) -> bool {
+        match self {
+            Self::Latest => true,
+            Self::Previous => false,
+            Self::Exact(pinned) => pinned == version,
+            Self::Range(req) => req.matches(version),
+        }
+    }
+}
+
+/// Release stream to follow.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Published releases only.
+    #[default]
+    Stable,
+    /// Release candidates and other pre-releases as well.
+    Prerelease,
+}
+
+impl Channel {
+    /// Prefix of the extract dir for installs from this
+    /// channel. Pre-releases live apart so switching back to
+    /// stable never picks up an RC binary.
+    pub fn dir_prefix(self) -> &'static str {
+        match self {
+            Self::Stable => "shebe-",
+            Self::Prerelease => "shebe-prerelease-",
+        }
+    }
+}
+
+/// Everything that decides which release asset gets
+/// installed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReleaseQuery {
+    pub source: ReleaseSource,
+    pub version: VersionSpec,
+    pub channel: Channel,
+    pub libc: Libc,
+}
+
+/// The outcome of one release lookup.
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    /// The release `query` selects.
+    pub release: Release,
+    /// Tag of the newest release on the query's channel seen
+    /// by the same lookup, whatever the `version` pin, so a
+    /// held-back update can be reported without a second
+    /// lookup.
+    pub newest: Option<String>,
+}
+
+/// Resolve `query` to a concrete release from its source.
+pub fn resolve(query: &ReleaseQuery) -> zed::Result<Resolution> {
+    match &query.source {
+        ReleaseSource::GitHub { repo } => {
+            resolve_github(repo, query)
+        }
+        ReleaseSource::Mirror { manifest_url } => {
+            resolve_manifest(manifest_url, query)
+        }
+    }
+}
+
+/// `Latest` uses the extension API's latest-release lookup.
+/// Pins are resolved against the release list, which also
+/// yields the newest release; an exact tag older than the
+/// list reaches is looked up by name.
+fn resolve_github(
+    repo: &str,
+    query: &ReleaseQuery,
+) -> zed::Result<Resolution> {
+    let pre_release = query.channel == Channel::Prerelease;
+    let req = match &query.version {
+        VersionSpec::Latest => {
+            let release = api_release(zed::latest_github_release(
+                repo,
+                zed::GithubReleaseOptions {
+                    require_assets: true,
+                    pre_release,
+                },
+            )?);
+            return Ok(Resolution {
+                newest: Some(release.version.clone()),
+                release,
+            });
+        }
+        VersionSpec::Exact(version) => {
+            VersionReq::parse(&format!("={}", version))
+                .map_err(|e| e.to_string())?
+        }
+        VersionSpec::Range(req) => req.clone(),
+        VersionSpec::Previous => return Err(previous_error()),
+    };
+
+    let candidates: Vec<Candidate> = list_releases(repo)?
+        .into_iter()
+        .filter(|r| !r.draft)
+        .map(Candidate::from)
+        .collect();
+    let newest = newest_matching(
+        candidates.iter().cloned(),
+        &VersionReq::STAR,
+        pre_release,
+    )
+    .map(|r| r.version);
+    let release = match newest_matching(candidates, &req, pre_release)
+    {
+        Some(release) => release,
+        None => match &query.version {
+            VersionSpec::Exact(version) => api_release(
+                zed::github_release_by_tag_name(
+                    repo,
+                    &format!("v{}", version),
+                )?,
+            ),
+            _ => {
+                return Err(format!(
+                    "no release of {} matches version '{}'",
+                    repo, req,
+                ))
+            }
+        },
+    };
+    Ok(Resolution { release, newest })
+}
+
+fn api_release(release: zed::GithubRelease) -> Release {
+    Release {
+        version: release.version,
+        assets: release
+            .assets
+            .into_iter()
+            .map(|a| Asset {
+                name: a.name,
+                download_url: a.download_url,
+                sha256: None,
+            })
+            .collect(),
+    }
+}
+
+/// Resolve `query` against the release manifest at
+/// `manifest_url`.
+fn resolve_manifest(
+    manifest_url: &str,
+    query: &ReleaseQuery,
+) -> zed::Result<Resolution> {
+    let manifest = fetch_manifest(manifest_url)?;
+    select_from_manifest(&manifest, manifest_url, query)
+}
+
+/// Pick the release `query` selects from `manifest`,
+/// skipping releases that need a newer extension. Asset
+/// URLs are resolved against `manifest_url`.
+pub fn select_from_manifest(
+    manifest: &Manifest,
+    manifest_url: &str,
+    query: &ReleaseQuery,
+) -> Result<Resolution, String> {
+    let pre_release = query.channel == Channel::Prerelease;
+    let extension = Version::parse(EXTENSION_VERSION)
+        .map_err(|e| e.to_string())?;
+    let candidates: Vec<Candidate> = manifest
+        .releases
+        .iter()
+        .filter(|r| r.supports(&extension))
+        .map(|r| Candidate {
+            prerelease: r.prerelease,
+            release: r.to_release(manifest_url),
+        })
+        .collect();
+    let newest = newest_matching(
+        candidates.iter().cloned(),
+        &VersionReq::STAR,
+        pre_release,
+    );
+
+    let found = match &query.version {
+        VersionSpec::Latest => newest.clone(),
+        VersionSpec::Exact(version) => {
+            candidates.into_iter().map(|c| c.release).find(|r| {
+                tag_version(&r.version).as_ref() == Some(version)
+            })
+        }
+        VersionSpec::Range(req) => {
+            newest_matching(candidates, req, pre_release)
+        }
+        VersionSpec::Previous => return Err(previous_error()),
+    };
+    let release = found.ok_or_else(|| {
+        format!(
+            "no release in {} matches version '{}' for \
+             extension v{}",
+            manifest_url, query.version, EXTENSION_VERSION,
+        )
+    })?;
+    Ok(Resolution {
+        release,
+        newest: newest.map(|r| r.version),
+    })
+}
+
+fn previous_error() -> String {
+    "version \"previous\" is resolved from installed \
+     versions, not from releases"
+        .into()
+}
+
+/// Parse a `v`-prefixed release tag.
+fn tag_version(tag: &str) -> Option<Version> {
+    Version::parse(tag.trim_start_matches('v')).ok()
+}
+
+#[derive(Deserialize)]
+struct ApiRelease {
+    tag_name: String,
+    draft: bool,
+    prerelease: bool,
+    assets: Vec<ApiAsset>,
+}
+
+#[derive(Deserialize)]
+struct ApiAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// A release considered by [`newest_matching`].
+#[derive(Clone)]
+struct Candidate {
+    prerelease: bool,
+    release: Release,
+}
+
+impl From<ApiRelease> for Candidate {
+    fn from(r: ApiRelease) -> Self {
+        Self {
+            prerelease: r.prerelease,
+            release: Release {
+                version: r.tag_name,
+                assets: r
+                    .assets
+                    .into_iter()
+                    .map(|a| Asset {
+                        name: a.name,
+                        download_url: a.browser_download_url,
+                        sha256: None,
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Fetch the most recent page of releases for `repo`.
+///
+/// The extension API only exposes "latest" and "by tag", so
+/// ranges go through the REST endpoint directly.
+fn list_releases(repo: &str) -> zed::Result<Vec<ApiRelease>> {
+    let url = format!(
+        "{}/repos/{}/releases?per_page=100",
+        GITHUB_API, repo,
+    );
+    let body = get(&url, "application/vnd.github+json")
+        .map_err(|e| {
+            format!("failed to list releases of {}: {}", repo, e)
+        })?;
+
+    serde_json::from_slice(&body).map_err(|e| {
+        format!(
+            "unexpected release list from {}: {}",
+            url, e,
+        )
+    })
+}
+
+fn fetch_manifest(url: &str) -> zed::Result<Manifest> {
+    let body = get(url, "application/json").map_err(|e| {
+        format!("failed to fetch {}: {}", url, e)
+    })?;
+    let json = String::from_utf8(body).map_err(|e| {
+        format!("release manifest {} is not UTF-8: {}", url, e)
+    })?;
+    Manifest::parse(&json)
+        .map_err(|e| format!("{} ({})", e, url))
+}
+
+/// GET `url` and return the response body.
+///
+/// The extension API's `HttpResponse` carries no status
+/// code, so a failed request is recognised by its body:
+/// GitHub answers a 403 (rate limit) or 404 with an object
+/// holding a `message` and the `status`, which becomes the
+/// error instead of surfacing later as a parse failure.
+fn get(url: &str, accept: &str) -> zed::Result<Vec<u8>> {
+    let response = HttpRequest::builder()
+        .method(HttpMethod::Get)
+        .url(url)
+        .header("Accept", accept)
+        .header("User-Agent", "zed-shebe")
+        .redirect_policy(RedirectPolicy::FollowAll)
+        .build()?
+        .fetch()?;
+    if let Some(error) = error_response(&response.body) {
+        let rate_limited = response.headers.iter().any(|(k, v)| {
+            k.eq_ignore_ascii_case("x-ratelimit-remaining")
+                && v.trim() == "0"
+        });
+        return Err(if rate_limited {
+            format!("{} (GitHub API rate limit exceeded)", error)
+        } else {
+            error
+        });
+    }
+    Ok(response.body)
+}
+
+/// Describe a GitHub-style error body, e.g.
+/// `{"message": "Not Found", "status": "404"}`, as
+/// `HTTP 404: Not Found`.
+fn error_response(body: &[u8]) -> Option<String> {
+    let value: serde_json::Value =
+        serde_json::from_slice(body).ok()?;
+    let object = value.as_object()?;
+    let message = object.get("message")?.as_str()?;
+    if object.contains_key("schema_version") {
+        return None;
+    }
+    Some(match object.get("status").and_then(|s| s.as_str()) {
+        Some(status) => format!("HTTP {}: {}", status, message),
+        None => message.to_string(),
+    })
+}
+
+/// Pick the highest release matching `req`. With
+/// `pre_release` set, a pre-release matches when its base
+/// version does, so `~0.6` also selects `v0.6.0-rc.1`.
+fn newest_matching(
+    candidates: impl IntoIterator<Item = Candidate>,
+    req: &VersionReq,
+    pre_release: bool,
+) -> Option<Release> {
+    candidates
+        .into_iter()
+        .filter(|c| {
+            (pre_release || !c.prerelease)
+                && !c.release.assets.is_empty()
+        })
+        .filter_map(|c| {
+            let version = tag_version(&c.release.version)?;
+            let mut base = version.clone();
+            if pre_release {
+                base.pre = semver::Prerelease::EMPTY;
+            }
+            req.matches(&base).then_some((version, c.release))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(tag: &str, prerelease: bool) -> Candidate {
+        Candidate {
+            prerelease,
+            release: Release {
+                version: tag.into(),
+                assets: vec![Asset {
+                    name: format!("shebe-{}-linux-x86_64.tar.gz", tag),
+                    download_url: String::new(),
+                    sha256: None,
+                }],
+            },
+        }
+    }
+
+    fn newest(
+        tags: &[(&str, bool)],
+        req: &str,
+        pre_release: bool,
+    ) -> Option<String> {
+        let candidates =
+            tags.iter().map(|(tag, pre)| candidate(tag, *pre));
+        let req = VersionReq::parse(req).unwrap();
+        newest_matching(candidates, &req, pre_release)
+            .map(|r| r.version)
+    }
+
+    #[test]
+    fn parses_version_specs() {
+        let parse = |s: &str| s.parse::<VersionSpec>();
+        assert_eq!(parse("latest"), Ok(VersionSpec::Latest));
+        assert_eq!(parse(""), Ok(VersionSpec::Latest));
+        assert_eq!(parse("previous"), Ok(VersionSpec::Previous));
+        let exact = VersionSpec::Exact(Version::new(0, 5, 7));
+        assert_eq!(parse("v0.5.7"), Ok(exact.clone()));
+        assert_eq!(parse("0.5.7"), Ok(exact));
+        assert_eq!(
+            parse("~0.5"),
+            Ok(VersionSpec::Range(VersionReq::parse("~0.5").unwrap())),
+        );
+        let range = parse(">=0.5, <0.6").unwrap();
+        assert!(range.matches(&Version::new(0, 5, 9)));
+        assert!(!range.matches(&Version::new(0, 6, 0)));
+
+        let err = parse("newest").unwrap_err();
+        assert!(err.contains("invalid version 'newest'"), "{}", err);
+    }
+
+    #[test]
+    fn picks_highest_matching_release() {
+        let tags = [
+            ("v0.5.2", false),
+            ("v0.6.1", false),
+            ("v0.5.10", false),
+            ("v0.5.9", false),
+            ("not-a-version", false),
+        ];
+        assert_eq!(newest(&tags, "~0.5", false).unwrap(), "v0.5.10");
+        assert_eq!(newest(&tags, "*", false).unwrap(), "v0.6.1");
+        assert_eq!(newest(&tags, "^1", false), None);
+    }
+
+    #[test]
+    fn prerelease_channel_matches_base_version() {
+        let tags = [
+            ("v0.5.7", false),
+            ("v0.6.0-rc.1", true),
+            ("v0.6.0-rc.2", true),
+            ("v0.7.0-rc.1", true),
+        ];
+        assert_eq!(newest(&tags, "~0.6", true).unwrap(), "v0.6.0-rc.2");
+        assert_eq!(newest(&tags, "*", true).unwrap(), "v0.7.0-rc.1");
+        assert_eq!(newest(&tags, "=0.5.7", true).unwrap(), "v0.5.7");
+    }
+
+    #[test]
+    fn stable_channel_never_picks_prereleases() {
+        let tags = [
+            ("v0.5.7", false),
+            ("v0.6.0-rc.1", true),
+            // Tagged like a release but marked as a pre-release.
+            ("v0.6.0", true),
+        ];
+        assert_eq!(newest(&tags, "*", false).unwrap(), "v0.5.7");
+        assert_eq!(newest(&tags, "~0.6", false), None);
+        assert_eq!(Channel::Stable.dir_prefix(), "shebe-");
+        assert_eq!(
+            Channel::Prerelease.dir_prefix(),
+            "shebe-prerelease-",
+        );
+    }
+
+    #[test]
+    fn skips_releases_without_assets() {
+        let mut empty = candidate("v0.5.9", false);
+        empty.release.assets.clear();
+        let found = newest_matching(
+            [candidate("v0.5.7", false), empty],
+            &VersionReq::STAR,
+            false,
+        );
+        assert_eq!(found.unwrap().version, "v0.5.7");
+    }
+
+    #[test]
+    fn manifest_lookup_reports_newest_release() {
+        let release = |version: &str| crate::manifest::ManifestRelease {
+            version: version.into(),
+            prerelease: false,
+            min_extension_version: None,
+            targets: [(
+                "linux-x86_64-musl".to_string(),
+                crate::manifest::Target {
+                    url: format!("{}/shebe.tar.gz", version),
+                    sha256: "0".repeat(64),
+                },
+            )]
+            .into(),
+        };
+        let manifest = Manifest {
+            schema_version: crate::manifest::SCHEMA_VERSION,
+            releases: vec![release("v0.6.0"), release("v0.5.7")],
+        };
+        let query = ReleaseQuery {
+            version: "~0.5".parse().unwrap(),
+            ..ReleaseQuery::default()
+        };
+        let resolution = select_from_manifest(
+            &manifest,
+            "https://mirror.example/releases.json",
+            &query,
+        )
+        .unwrap();
+        assert_eq!(resolution.release.version, "v0.5.7");
+        assert_eq!(resolution.newest.as_deref(), Some("v0.6.0"));
+    }
+
+    #[test]
+    fn describes_github_error_bodies() {
+        let body = br#"{"message":"API rate limit exceeded","status":"403"}"#;
+        assert_eq!(
+            error_response(body).unwrap(),
+            "HTTP 403: API rate limit exceeded",
+        );
+        assert_eq!(
+            error_response(br#"{"message":"Not Found"}"#).unwrap(),
+            "Not Found",
+        );
+        assert_eq!(error_response(b"[]"), None);
+        assert_eq!(
+            error_response(br#"{"schema_version":1,"message":"hi"}"#),
+            None,
+        );
+    }
+}
@@ -0,0 +1,557 @@
+//! Typed view of `context_servers.shebe.settings`.
+//!
+//! Zed hands the extension an untyped JSON value; this module
+//! deserializes it once so the rest of the extension works
+//! with plain fields and defaults.
+//!
+//! The shebe tuning options (`chunk_size`, `overlap`,
+//! `default_k`, `max_k`, `data_dir`) are validated against the
+//! documented ranges here and passed to `shebe-mcp` as
+//! `SHEBE_*` environment variables. Anything else goes
+//! through `args` and `env`, which may reference
+//! [`Placeholder`]s.
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use serde::Deserialize;
+use zed_extension_api::serde_json::{self, Value};
+
+use crate::platform::Libc;
+use crate::release::{Channel, ReleaseQuery, VersionSpec};
+use crate::source::ReleaseSource;
+
+/// Extension-level settings read from
+/// `context_servers.shebe.settings` in Zed's `settings.json`.
+/// Unknown keys are rejected, so a misspelled setting is
+/// reported instead of silently left at its default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShebeSettings {
+    /// Run this `shebe-mcp` instead of resolving one. The
+    /// `settings` counterpart of `command.path`, so it can be
+    /// set from the configuration UI.
+    pub binary_path: Option<String>,
+    /// Prefer a `shebe-mcp` already on `PATH` (e.g. from the
+    /// Homebrew formula) over downloading a private copy.
+    pub path_lookup: bool,
+    /// GitHub `owner/repo` or static mirror URL to discover
+    /// and download releases from.
+    pub release_source: ReleaseSource,
+    /// Release to install: `"latest"`, an exact tag or a
+    /// semver range.
+    pub version: VersionSpec,
+    /// `"stable"` or `"prerelease"`.
+    pub channel: Channel,
+    /// Linux build flavour: `"auto"`, `"musl"` or `"gnu"`.
+    pub libc: Libc,
+    /// Number of installed versions to keep per channel for
+    /// rollback. Older `shebe-*` dirs are removed after a
+    /// successful install.
+    pub keep_versions: usize,
+    /// Hours between release lookups while an installed
+    /// binary is available. 0 disables the periodic check.
+    pub update_check_hours: u64,
+    /// `SHEBE_CHUNK_SIZE`: characters per chunk (100-2000).
+    pub chunk_size: Option<u32>,
+    /// `SHEBE_OVERLAP`: overlap between chunks, smaller than
+    /// the chunk size.
+    pub overlap: Option<u32>,
+    /// `SHEBE_DEFAULT_K`: default number of search results.
+    pub default_k: Option<u32>,
+    /// `SHEBE_MAX_K`: maximum number of search results.
+    pub max_k: Option<u32>,
+    /// `SHEBE_DATA_DIR`: index storage location.
+    pub data_dir: Option<String>,
+    /// Keep a separate index per project instead of sharing
+    /// `SHEBE_DATA_DIR` across all of them.
+    pub project_data_dir: ProjectDataDir,
+    /// Index the worktree in the background when the context
+    /// server starts and no session for it exists yet.
+    pub auto_index: bool,
+    /// Skip auto-indexing worktrees whose non-ignored files
+    /// exceed this many megabytes. 0 disables the cap.
+    pub auto_index_max_mb: u64,
+    /// Re-index an auto-indexed session at startup once it
+    /// is this many hours old. 0 never re-indexes.
+    pub auto_reindex_hours: u64,
+    /// Extra `shebe-mcp` arguments, after any
+    /// `command.arguments`.
+    pub args: Vec<String>,
+    /// Extra environment variables, layered over the
+    /// `SHEBE_*` ones derived from the settings above and
+    /// under `command.env`.
+    pub env: BTreeMap<String, String>,
+}
+
+/// Where per-project indexes live, if anywhere.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectDataDir {
+    /// Share shebe's global data dir.
+    #[default]
+    Off,
+    /// `.shebe/` at the worktree root.
+    Worktree,
+    /// A per-project directory under the extension work dir.
+    Extension,
+}
+
+/// A `${name}` variable in `args` and `env` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `${worktree_root}`: the project root.
+    WorktreeRoot,
+    /// `${extension_dir}`: the extension work dir.
+    ExtensionDir,
+    /// `${home}`: the user's home directory.
+    Home,
+}
+
+impl Placeholder {
+    const ALL: [Self; 3] =
+        [Self::WorktreeRoot, Self::ExtensionDir, Self::Home];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::WorktreeRoot => "worktree_root",
+            Self::ExtensionDir => "extension_dir",
+            Self::Home => "home",
+        }
+    }
+}
+
+/// A piece of a templated `args` or `env` value.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Piece<'a> {
+    Text(&'a str),
+    Var(Placeholder),
+}
+
+/// Split `value` into text and placeholders. Every `${`
+/// must open a known placeholder; a `$` not followed by `{`
+/// is kept as is.
+pub fn template(value: &str) -> Result<Vec<Piece<'_>>, String> {
+    let mut pieces = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        if start > 0 {
+            pieces.push(Piece::Text(&rest[..start]));
+        }
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            return Err(format!("unterminated ${{ in '{}'", value));
+        };
+        let name = &after[..end];
+        let var = Placeholder::ALL
+            .into_iter()
+            .find(|p| p.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown placeholder ${{{}}} in '{}' (expected \
+                     worktree_root, extension_dir or home)",
+                    name, value,
+                )
+            })?;
+        pieces.push(Piece::Var(var));
+        rest = &after[end + 1..];
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    Ok(pieces)
+}
+
+/// Documented bounds for `SHEBE_CHUNK_SIZE`.
+const CHUNK_SIZE_RANGE: RangeInclusive<u32> = 100..=2000;
+
+/// shebe's own defaults, used to cross-check related settings
+/// when only one side is configured.
+const DEFAULT_CHUNK_SIZE: u32 = 512;
+const DEFAULT_MAX_K: u32 = 100;
+
+impl Default for ShebeSettings {
+    fn default() -> Self {
+        Self {
+            binary_path: None,
+            path_lookup: true,
+            release_source: ReleaseSource::default(),
+            version: VersionSpec::Latest,
+            channel: Channel::Stable,
+            libc: Libc::Auto,
+            keep_versions: 2,
+            update_check_hours: 24,
+            chunk_size: None,
+            overlap: None,
+            default_k: None,
+            max_k: None,
+            data_dir: None,
+            project_data_dir: ProjectDataDir::Off,
+            auto_index: false,
+            auto_index_max_mb: 500,
+            auto_reindex_hours: 24,
+            args: Vec::new(),
+            env: BTreeMap::new(),
+        }
+    }
+}
+
+impl ShebeSettings {
+    /// Parse the raw `settings` value. A missing or `null`
+    /// block yields the defaults.
+    pub fn from_value(
+        value: Option<Value>,
+    ) -> Result<Self, String> {
+        let settings: Self = match value {
+            None | Some(Value::Null) => Self::default(),
+            Some(value) => serde_json::from_value(value)
+                .map_err(|e| {
+                    format!(
+                        "invalid context_servers.shebe.settings: {}",
+                        e,
+                    )
+                })?,
+        };
+        settings.validate().map_err(|e| {
+            format!("invalid context_servers.shebe.settings: {}", e)
+        })?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(chunk_size) = self.chunk_size {
+            if !CHUNK_SIZE_RANGE.contains(&chunk_size) {
+                return Err(format!(
+                    "chunk_size must be between {} and {} (got {})",
+                    CHUNK_SIZE_RANGE.start(),
+                    CHUNK_SIZE_RANGE.end(),
+                    chunk_size,
+                ));
+            }
+        }
+
+        if let Some(overlap) = self.overlap {
+            let chunk_size =
+                self.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+            if overlap >= chunk_size {
+                return Err(format!(
+                    "overlap must be smaller than chunk_size \
+                     ({}), got {}",
+                    chunk_size, overlap,
+                ));
+            }
+        }
+
+        if self.max_k == Some(0) {
+            return Err("max_k must be at least 1".into());
+        }
+
+        if let Some(default_k) = self.default_k {
+            let max_k = self.max_k.unwrap_or(DEFAULT_MAX_K);
+            if default_k == 0 || default_k > max_k {
+                return Err(format!(
+                    "default_k must be between 1 and max_k \
+                     ({}), got {}",
+                    max_k, default_k,
+                ));
+            }
+        }
+
+        if self.data_dir.as_deref().is_some_and(str::is_empty) {
+            return Err("data_dir must not be empty".into());
+        }
+
+        if self.data_dir.is_some()
+            && self.project_data_dir != ProjectDataDir::Off
+        {
+            return Err(
+                "data_dir and project_data_dir cannot both be set"
+                    .into(),
+            );
+        }
+
+        if self.binary_path.as_deref().is_some_and(str::is_empty)
+        {
+            return Err("binary_path must not be empty".into());
+        }
+
+        for arg in &self.args {
+            template(arg).map_err(|e| format!("args: {}", e))?;
+        }
+
+        for (key, value) in &self.env {
+            let valid = key
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(format!(
+                    "env: '{}' is not a valid variable name",
+                    key,
+                ));
+            }
+            template(value)
+                .map_err(|e| format!("env.{}: {}", key, e))?;
+        }
+
+        if self.env.contains_key("SHEBE_DATA_DIR")
+            && self.project_data_dir != ProjectDataDir::Off
+        {
+            return Err(
+                "env.SHEBE_DATA_DIR and project_data_dir cannot \
+                 both be set"
+                    .into(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Reject settings that conflict with the variables
+    /// `command.env` sets (`pinned`). A per-project data dir
+    /// would otherwise silently replace a pinned
+    /// `SHEBE_DATA_DIR`.
+    pub fn check_pinned(&self, pinned: &[String]) -> Result<(), String> {
+        if self.project_data_dir != ProjectDataDir::Off
+            && pinned.iter().any(|key| key == "SHEBE_DATA_DIR")
+        {
+            return Err(
+                "invalid context_servers.shebe: \
+                 command.env.SHEBE_DATA_DIR and \
+                 settings.project_data_dir cannot both be set"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the command these settings produce depends on
+    /// the worktree it runs for.
+    pub fn uses_worktree(&self) -> bool {
+        self.project_data_dir != ProjectDataDir::Off
+            || self.args.iter().chain(self.env.values()).any(|value| {
+                template(value).is_ok_and(|pieces| {
+                    pieces.contains(&Piece::Var(Placeholder::WorktreeRoot))
+                })
+            })
+    }
+
+    /// `SHEBE_*` environment variables for the configured
+    /// tuning options. Unset options are left to shebe's own
+    /// defaults and config file.
+    pub fn tuning_env(&self) -> Vec<(String, String)> {
+        let numeric = [
+            ("SHEBE_CHUNK_SIZE", self.chunk_size),
+            ("SHEBE_OVERLAP", self.overlap),
+            ("SHEBE_DEFAULT_K", self.default_k),
+            ("SHEBE_MAX_K", self.max_k),
+        ];
+
+        let mut env: Vec<(String, String)> = numeric
+            .into_iter()
+            .filter_map(|(key, value)| {
+                Some((key.to_string(), value?.to_string()))
+            })
+            .collect();
+        if let Some(data_dir) = &self.data_dir {
+            env.push(("SHEBE_DATA_DIR".into(), data_dir.clone()));
+        }
+        env
+    }
+
+    /// The part of the settings that selects a release.
+    pub fn release_query(&self) -> ReleaseQuery {
+        ReleaseQuery {
+            source: self.release_source.clone(),
+            version: self.version.clone(),
+            channel: self.channel,
+            libc: self.libc,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn error(settings: Value) -> String {
+        ShebeSettings::from_value(Some(settings)).unwrap_err()
+    }
+
+    #[test]
+    fn missing_or_null_settings_are_defaults() {
+        for value in [None, Some(Value::Null)] {
+            let settings = ShebeSettings::from_value(value).unwrap();
+            assert_eq!(settings.keep_versions, 2);
+            assert!(settings.path_lookup);
+            assert!(settings.tuning_env().is_empty());
+        }
+    }
+
+    #[test]
+    fn rejects_chunk_size_out_of_range() {
+        for chunk_size in [99, 2001] {
+            let err = error(json!({ "chunk_size": chunk_size }));
+            assert!(
+                err.contains("chunk_size must be between 100 and 2000"),
+                "{}",
+                err,
+            );
+        }
+        assert!(
+            ShebeSettings::from_value(Some(json!({ "chunk_size": 100 })))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn rejects_overlap_not_below_chunk_size() {
+        let err = error(json!({ "chunk_size": 200, "overlap": 200 }));
+        assert!(err.contains("smaller than chunk_size (200)"), "{}", err);
+        let err = error(json!({ "overlap": 512 }));
+        assert!(err.contains("smaller than chunk_size (512)"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_bad_result_counts() {
+        let err = error(json!({ "max_k": 0 }));
+        assert!(err.contains("max_k must be at least 1"), "{}", err);
+        let err = error(json!({ "max_k": 20, "default_k": 21 }));
+        assert!(err.contains("between 1 and max_k (20)"), "{}", err);
+        let err = error(json!({ "default_k": 0 }));
+        assert!(err.contains("between 1 and max_k (100)"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_empty_paths() {
+        let err = error(json!({ "data_dir": "" }));
+        assert!(err.contains("data_dir must not be empty"), "{}", err);
+        let err = error(json!({ "binary_path": "" }));
+        assert!(err.contains("binary_path must not be empty"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_conflicting_data_dirs() {
+        let err = error(json!({
+            "data_dir": "/data",
+            "project_data_dir": "worktree",
+        }));
+        assert!(err.contains("cannot both be set"), "{}", err);
+        let err = error(json!({
+            "env": { "SHEBE_DATA_DIR": "/data" },
+            "project_data_dir": "extension",
+        }));
+        assert!(err.contains("env.SHEBE_DATA_DIR"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_project_data_dir_with_pinned_data_dir() {
+        let pinned = ["SHEBE_DATA_DIR".to_string()];
+        let settings = |value| {
+            ShebeSettings::from_value(Some(value)).unwrap()
+        };
+        let err = settings(json!({ "project_data_dir": "worktree" }))
+            .check_pinned(&pinned)
+            .unwrap_err();
+        assert!(err.contains("command.env.SHEBE_DATA_DIR"), "{}", err);
+        assert!(settings(json!({ "project_data_dir": "off" }))
+            .check_pinned(&pinned)
+            .is_ok());
+        assert!(settings(json!({ "project_data_dir": "extension" }))
+            .check_pinned(&["SHEBE_LOG".to_string()])
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_args_and_env() {
+        let err = error(json!({ "env": { "RUST-LOG": "debug" } }));
+        assert!(
+            err.contains("'RUST-LOG' is not a valid variable name"),
+            "{}",
+            err,
+        );
+        let err = error(json!({ "env": { "1X": "y" } }));
+        assert!(err.contains("not a valid variable name"), "{}", err);
+        let err = error(json!({ "args": ["--root=${root}"] }));
+        assert!(err.contains("unknown placeholder ${root}"), "{}", err);
+        let err = error(json!({ "env": { "LOG": "${home/log" } }));
+        assert!(err.contains("env.LOG: unterminated ${"), "{}", err);
+    }
+
+    #[test]
+    fn errors_name_the_settings_block() {
+        let err = error(json!({ "chunk_size": "big" }));
+        assert!(
+            err.starts_with("invalid context_servers.shebe.settings"),
+            "{}",
+            err,
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        for key in ["update_check_hour", "releases_source"] {
+            let err = error(json!({ key: 1 }));
+            assert!(
+                err.contains(&format!("unknown field `{}`", key)),
+                "{}",
+                err,
+            );
+        }
+    }
+
+    #[test]
+    fn schema_lists_every_setting() {
+        let schema: Value = serde_json::from_str(include_str!(
+            "../configuration/settings_schema.json"
+        ))
+        .unwrap();
+        assert_eq!(schema["additionalProperties"], json!(false));
+        let settings: serde_json::Map<String, Value> = schema
+            ["properties"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(key, property)| {
+                let value = property
+                    .get("default")
+                    .cloned()
+                    .unwrap_or_else(|| json!("/opt/shebe"));
+                (key.clone(), value)
+            })
+            .collect();
+        serde_json::from_value::<ShebeSettings>(Value::Object(settings))
+            .unwrap();
+    }
+
+    #[test]
+    fn detects_worktree_dependent_settings() {
+        let uses = |value| {
+            ShebeSettings::from_value(Some(value))
+                .unwrap()
+                .uses_worktree()
+        };
+        assert!(!uses(json!({ "args": ["--home=${home}"] })));
+        assert!(uses(json!({ "project_data_dir": "worktree" })));
+        assert!(uses(json!({ "env": { "ROOT": "${worktree_root}" } })));
+    }
+
+    #[test]
+    fn splits_templates() {
+        assert_eq!(
+            template("${home}/x-${worktree_root}").unwrap(),
+            [
+                Piece::Var(Placeholder::Home),
+                Piece::Text("/x-"),
+                Piece::Var(Placeholder::WorktreeRoot),
+            ],
+        );
+        assert_eq!(template("$HOME").unwrap(), [Piece::Text("$HOME")]);
+    }
+}
@@ -0,0 +1,585 @@
+//! Assistant slash commands backed by `shebe-mcp`.
+//!
+//! Each command makes one tool call on a fresh `shebe-mcp`
+//! (see [`crate::mcp::OneShot`]) against the worktree's
+//! session, named
+//! after the worktree root directory, and turns the tool's
+//! markdown into sections Zed can fold and label.
+
+use std::time::{Duration, Instant};
+
+use zed_extension_api::{
+    self as zed, SlashCommandArgumentCompletion,
+    SlashCommandOutput, SlashCommandOutputSection,
+};
+
+use crate::mcp::{
+    self,
+    tools::{self, Tool},
+};
+
+/// Session name for the worktree at `root`: its directory
+/// name, reduced to characters shebe accepts in session ids.
+///
+/// Every other byte becomes a `-`, so a non-ASCII character
+/// turns into one `-` per UTF-8 byte. That is what the
+/// auto-index job's `LC_ALL=C tr` produces, and the two
+/// must agree on the name.
+pub fn session_name(root: &str) -> String {
+    let base = root.trim_end_matches('/').rsplit('/').next();
+    let name: String = base
+        .unwrap_or_default()
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b == b'_' || b == b'-' {
+                char::from(b)
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    match name.as_str() {
+        "" | "-" => "root".to_string(),
+        _ => name,
+    }
+}
+
+/// `/shebe <query>`: BM25 search of the worktree session.
+pub fn search(
+    server: &zed::Command,
+    root: &str,
+    args: &[String],
+) -> Result<SlashCommandOutput, String> {
+    let query = args.join(" ");
+    if query.trim().is_empty() {
+        return Err("usage: /shebe <query>".into());
+    }
+    let session = session_name(root);
+    let call = tools::SearchCode {
+        query: query.clone(),
+        session: session.clone(),
+        k: None,
+    };
+    let text = mcp::call(server, &call).map_err(|e| {
+        format!(
+            "search of session '{}' failed: {}. Run \
+             /shebe-index to index the worktree first.",
+            session, e,
+        )
+    })?;
+    Ok(search_output(&query, &text))
+}
+
+/// `/shebe-index`: index the worktree into its session.
+/// `/shebe-reindex` (`reindex: true`) refreshes an existing
+/// session from its stored repository path.
+pub fn index(
+    server: &zed::Command,
+    root: &str,
+    reindex: bool,
+) -> Result<SlashCommandOutput, String> {
+    let session = session_name(root);
+    let started = Instant::now();
+    let (tool, text) = if reindex {
+        let call = tools::ReindexSession {
+            session: session.clone(),
+        };
+        (tools::ReindexSession::NAME, mcp::call(server, &call))
+    } else {
+        let call = tools::IndexRepository {
+            path: root.to_string(),
+            session: session.clone(),
+            force: None,
+        };
+        (tools::IndexRepository::NAME, mcp::call(server, &call))
+    };
+    let text = text.map_err(|e| {
+        format!("{} of session '{}' failed: {}", tool, session, e)
+    })?;
+    let summary = index_summary(
+        &session,
+        root,
+        &text,
+        started.elapsed(),
+    );
+    Ok(single_section(format!("shebe: {}", session), &summary))
+}
+
+/// Summarise `index_repository` / `reindex_session` output.
+/// Counts the tool does not report are left out; the raw
+/// output is kept when neither count is found.
+fn index_summary(
+    session: &str,
+    root: &str,
+    text: &str,
+    elapsed: Duration,
+) -> String {
+    let mut summary = format!(
+        "Indexed {} into session `{}`\n\n",
+        root, session,
+    );
+    let files = count(text, "files");
+    let chunks = count(text, "chunks");
+    if let Some(files) = files {
+        summary.push_str(&format!("- Files: {}\n", files));
+    }
+    if let Some(chunks) = chunks {
+        summary.push_str(&format!("- Chunks: {}\n", chunks));
+    }
+    summary.push_str(&format!(
+        "- Elapsed: {:.1}s\n- Session: {}\n",
+        elapsed.as_secs_f64(),
+        session,
+    ));
+    if files.is_none() && chunks.is_none() {
+        summary.push('\n');
+        summary.push_str(text.trim());
+        summary.push('\n');
+    }
+    summary
+}
+
+/// The value of the first `label: number` line whose label
+/// mentions `key`, e.g. 1234 for `Files indexed: 1,234` or
+/// `- **Files indexed:** 1,234`. Numbers in prose, as in
+/// `Indexed 3 files`, are not counts.
+fn count(text: &str, key: &str) -> Option<u64> {
+    text.lines().find_map(|line| {
+        let (label, value) = line.split_once(':')?;
+        if !label.to_ascii_lowercase().contains(key) {
+            return None;
+        }
+        let value = value.trim_start_matches(['*', '`', ' ']);
+        let number: String = value
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == ',')
+            .filter(char::is_ascii_digit)
+            .collect();
+        number.parse().ok()
+    })
+}
+
+/// `/shebe-refs <symbol> [session]`: references to `symbol`
+/// in `session`, by default the worktree's.
+pub fn references(
+    server: &zed::Command,
+    root: &str,
+    args: &[String],
+) -> Result<SlashCommandOutput, String> {
+    let (symbol, session) = match args {
+        [symbol] => (symbol, session_name(root)),
+        [symbol, session] => (symbol, session.clone()),
+        _ => {
+            return Err(
+                "usage: /shebe-refs <symbol> [session]".into()
+            )
+        }
+    };
+    let call = tools::FindReferences {
+        symbol: symbol.clone(),
+        session: session.clone(),
+        max_results: None,
+    };
+    let text = mcp::call(server, &call).map_err(|e| {
+        format!(
+            "find_references in session '{}' failed: {}",
+            session, e,
+        )
+    })?;
+    Ok(references_output(symbol, &text))
+}
+
+/// Group `find_references` output by the confidence headings
+/// shebe emits (`### High Confidence (3)` ...), one section
+/// per group. Each group keeps shebe's per-reference pattern
+/// labels (type annotation, function call, ...).
+fn references_output(
+    symbol: &str,
+    text: &str,
+) -> SlashCommandOutput {
+    let is_group = |line: &str| {
+        line.starts_with('#')
+            && line.to_ascii_lowercase().contains("confidence")
+    };
+    let mut groups: Vec<(&str, Vec<&str>)> = Vec::new();
+    for line in text.lines() {
+        if is_group(line) {
+            let heading = line.trim_start_matches('#').trim();
+            groups.push((heading, Vec::new()));
+        } else if let Some((_, lines)) = groups.last_mut() {
+            lines.push(line);
+        }
+    }
+    if groups.is_empty() {
+        return single_section(format!("refs: {}", symbol), text);
+    }
+
+    let mut output = SlashCommandOutput {
+        text: String::new(),
+        sections: Vec::new(),
+    };
+    for (heading, lines) in groups {
+        let start = output.text.len() as u32;
+        output.text.push_str(heading);
+        output.text.push('\n');
+        output.text.push_str(lines.join("\n").trim_end());
+        output.text.push('\n');
+        output.sections.push(SlashCommandOutputSection {
+            range: (start..output.text.len() as u32).into(),
+            label: format!("{}: {}", symbol, heading),
+        });
+    }
+    output
+}
+
+/// Session names in `list_sessions` output.
+///
+/// A session is a list item or `##` heading starting with
+/// its name as a bold or code span, or a list item that is
+/// just the name, optionally followed by `(details)`. Names
+/// that are not valid session ids (see [`session_name`]) are
+/// skipped, so prose around the list is never offered as a
+/// session.
+pub fn session_names(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim_start)
+        .filter_map(|line| {
+            let (item, bare) = match line.strip_prefix("## ") {
+                Some(heading) => (heading, false),
+                None => (
+                    line.strip_prefix("- ")
+                        .or_else(|| line.strip_prefix("* "))?,
+                    true,
+                ),
+            };
+            let span = ["**", "`"].iter().find_map(|mark| {
+                let rest = item.strip_prefix(mark)?;
+                rest.split_once(mark).map(|(name, _)| name)
+            });
+            let name = match span {
+                Some(name) => name,
+                None if bare => match item.split_once(' ') {
+                    None => item.trim_end(),
+                    Some((name, details))
+                        if details.trim_start().starts_with('(') =>
+                    {
+                        name
+                    }
+                    Some(_) => return None,
+                },
+                None => return None,
+            };
+            let valid = !name.is_empty()
+                && name.bytes().all(|b| {
+                    b.is_ascii_alphanumeric() || b == b'_' || b == b'-'
+                });
+            valid.then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Completions for `/shebe-refs`: recently searched symbols
+/// for the first argument, sessions for the second. Both
+/// run the command when accepted.
+pub fn reference_completions(
+    args: &[String],
+    recent_symbols: &[String],
+    sessions: &[String],
+) -> Vec<SlashCommandArgumentCompletion> {
+    let (candidates, prefix) = match args {
+        [] => (recent_symbols, ""),
+        [symbol] => (recent_symbols, symbol.as_str()),
+        [_, session] => (sessions, session.as_str()),
+        _ => return Vec::new(),
+    };
+    let prefix = prefix.to_ascii_lowercase();
+    // A symbol can still be followed by a session, so only
+    // completing the session runs the command.
+    let run_command = args.len() == 2;
+    candidates
+        .iter()
+        .filter(|c| c.to_ascii_lowercase().starts_with(&prefix))
+        .map(|c| SlashCommandArgumentCompletion {
+            label: c.clone(),
+            new_text: c.clone(),
+            run_command,
+        })
+        .collect()
+}
+
+/// One ranked hit in `search_code` output.
+#[derive(Debug, PartialEq, Eq)]
+struct Hit<'a> {
+    /// `file:line` or just `file` when no lines are given.
+    label: String,
+    /// The hit's markdown, heading excluded.
+    body: &'a str,
+}
+
+/// Split `search_code` markdown into hits. Each result is a
+/// `## ` heading followed by a `**File:** \`path\`` line,
+/// optionally with a `(lines a-b)` range, and the snippet.
+fn hits(text: &str) -> Vec<Hit<'_>> {
+    let mut hits = Vec::new();
+    for block in text.split("\n## ").skip(1) {
+        let body = block.split_once('\n').map_or("", |(_, b)| b);
+        let Some(file_line) =
+            body.lines().find(|l| l.contains("**File:**"))
+        else {
+            continue;
+        };
+        let Some(path) = file_line.split('`').nth(1) else {
+            continue;
+        };
+        let label = match first_line(file_line) {
+            Some(line) => format!("{}:{}", path, line),
+            None => path.to_string(),
+        };
+        hits.push(Hit {
+            label,
+            body: body.trim_end(),
+        });
+    }
+    hits
+}
+
+/// The first line number in a `(line 12)` or
+/// `(lines 12-40)` annotation.
+fn first_line(file_line: &str) -> Option<u32> {
+    let (_, rest) = file_line.split_once("line")?;
+    let digits: String = rest
+        .trim_start_matches('s')
+        .trim_start()
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+/// Lay out `search_code` output as one section per hit. Text
+/// that does not look like search hits is inserted whole.
+fn search_output(query: &str, text: &str) -> SlashCommandOutput {
+    let hits = hits(text);
+    if hits.is_empty() {
+        return single_section(
+            format!("shebe: {}", query),
+            text,
+        );
+    }
+
+    let mut output = SlashCommandOutput {
+        text: String::new(),
+        sections: Vec::new(),
+    };
+    for hit in hits {
+        let start = output.text.len() as u32;
+        output.text.push_str(&hit.label);
+        output.text.push('\n');
+        output.text.push_str(hit.body);
+        output.text.push('\n');
+        output.sections.push(SlashCommandOutputSection {
+            range: (start..output.text.len() as u32).into(),
+            label: hit.label,
+        });
+    }
+    output
+}
+
+/// `text` as a single section labelled `label`.
+pub fn single_section(
+    label: String,
+    text: &str,
+) -> SlashCommandOutput {
+    let mut text = text.trim_end().to_string();
+    text.push('\n');
+    SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len() as u32).into(),
+            label,
+        }],
+        text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESULTS: &str = "\
+Found 2 results for query 'parse' (3ms):
+
+## Result 1 (score: 8.41)
+**File:** `src/parser.rs` (lines 42-67)
+```rust
+fn parse() {}
+```
+
+## Result 2 (score: 5.02)
+**File:** `README.md`
+Parsing is lazy.
+";
+
+    #[test]
+    fn session_is_named_after_worktree_dir() {
+        assert_eq!(session_name("/src/shebe-releases"), "shebe-releases");
+        assert_eq!(session_name("/src/My Repo.git/"), "My-Repo-git");
+        assert_eq!(session_name("/"), "root");
+        assert_eq!(session_name("/src/Mÿ App"), "M---App");
+    }
+
+    #[test]
+    fn splits_results_into_labelled_hits() {
+        let hits = hits(RESULTS);
+        let labels: Vec<_> =
+            hits.iter().map(|h| h.label.as_str()).collect();
+        assert_eq!(labels, ["src/parser.rs:42", "README.md"]);
+        assert!(hits[0].body.ends_with("fn parse() {}\n```"));
+    }
+
+    #[test]
+    fn sections_cover_each_hit() {
+        let output = search_output("parse", RESULTS);
+        assert_eq!(output.sections.len(), 2);
+        let first = &output.sections[0];
+        let text = &output.text
+            [first.range.start as usize..first.range.end as usize];
+        assert!(text.starts_with("src/parser.rs:42\n**File:**"));
+        assert_eq!(
+            output.sections[1].range.end as usize,
+            output.text.len(),
+        );
+    }
+
+    #[test]
+    fn summarises_index_counts() {
+        let text = "Indexing complete\nFiles indexed: 1,234\n\
+                    Chunks created: 5678\nDuration: 2.1s";
+        let summary = index_summary(
+            "app",
+            "/src/app",
+            text,
+            Duration::from_millis(2340),
+        );
+        assert_eq!(
+            summary,
+            "Indexed /src/app into session `app`\n\n\
+             - Files: 1234\n- Chunks: 5678\n\
+             - Elapsed: 2.3s\n- Session: app\n",
+        );
+    }
+
+    #[test]
+    fn counts_only_labelled_numbers() {
+        let text = "- **Files indexed:** 1,234\n- `Chunks`: 56\n";
+        assert_eq!(count(text, "files"), Some(1234));
+        assert_eq!(count(text, "chunks"), Some(56));
+        let prose = "Indexed 3 files into 2 chunks: done";
+        assert_eq!(count(prose, "files"), None);
+        assert_eq!(count(prose, "chunks"), None);
+        assert_eq!(count("Files: none", "files"), None);
+    }
+
+    #[test]
+    fn keeps_raw_index_output_without_counts() {
+        let summary = index_summary(
+            "app",
+            "/src/app",
+            "Session already up to date",
+            Duration::ZERO,
+        );
+        assert!(summary.ends_with("\nSession already up to date\n"));
+    }
+
+    #[test]
+    fn groups_references_by_confidence() {
+        let text = "\
+## References to `Policy` (3 found)
+
+### High Confidence (2)
+- `src/auth.rs:12` type annotation
+- `src/auth.rs:40` instantiation
+
+### Low Confidence (1)
+- `docs/auth.md:3` word match
+";
+        let output = references_output("Policy", text);
+        let labels: Vec<_> =
+            output.sections.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "Policy: High Confidence (2)",
+                "Policy: Low Confidence (1)",
+            ],
+        );
+        assert!(output.text.starts_with(
+            "High Confidence (2)\n- `src/auth.rs:12` type annotation\n",
+        ));
+    }
+
+    #[test]
+    fn parses_session_names() {
+        let text = "\
+Found 3 sessions:
+
+- **shebe-releases** (120 files, 900 chunks)
+- `my-app`: 42 files
+* scratch (empty)
+";
+        assert_eq!(
+            session_names(text),
+            ["shebe-releases", "my-app", "scratch"],
+        );
+    }
+
+    #[test]
+    fn skips_text_that_is_not_a_session() {
+        let text = "\
+## Sessions (2)
+
+## `app`
+- Files: 120
+- **Last indexed:** 2026-01-01
+- path /src/app
+- see the docs.
+";
+        assert_eq!(session_names(text), ["app"]);
+    }
+
+    #[test]
+    fn completes_symbols_then_sessions() {
+        let recent = vec!["Policy".to_string(), "parse".to_string()];
+        let sessions = vec!["app".to_string(), "lib".to_string()];
+        let labels = |args: &[&str]| -> Vec<String> {
+            let args: Vec<String> =
+                args.iter().map(|a| a.to_string()).collect();
+            reference_completions(&args, &recent, &sessions)
+                .into_iter()
+                .map(|c| c.label)
+                .collect()
+        };
+        assert_eq!(labels(&[]), ["Policy", "parse"]);
+        assert_eq!(labels(&["pa"]), ["parse"]);
+        assert_eq!(labels(&["Policy", ""]), ["app", "lib"]);
+        assert_eq!(labels(&["Policy", "l"]), ["lib"]);
+
+        let runs = |args: &[&str]| -> Vec<bool> {
+            let args: Vec<String> =
+                args.iter().map(|a| a.to_string()).collect();
+            reference_completions(&args, &recent, &sessions)
+                .into_iter()
+                .map(|c| c.run_command)
+                .collect()
+        };
+        assert_eq!(runs(&["pa"]), [false]);
+        assert_eq!(runs(&["Policy", "l"]), [true]);
+    }
+
+    #[test]
+    fn unrecognised_output_is_one_section() {
+        let output = search_output("parse", "No results found.");
+        assert_eq!(output.text, "No results found.\n");
+        assert_eq!(output.sections.len(), 1);
+        assert_eq!(output.sections[0].label, "shebe: parse");
+    }
+}
@@ -0,0 +1,169 @@
+//! Where releases are discovered and downloaded from.
+//!
+//! The `release_source` setting names either a GitHub
+//! repository (`owner/repo`, default [`DEFAULT_REPO`]) or a
+//! static mirror serving a [release manifest]. A mirror is
+//! given by the URL of its manifest, or by its base URL when
+//! the manifest is named [`MANIFEST_FILE`]:
+//!
+//! ```text
+//! https://mirror.example.com/shebe            -> .../shebe/releases.json
+//! https://mirror.example.com/shebe/stable.json
+//! ```
+//!
+//! [release manifest]: crate::manifest
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::manifest::MANIFEST_FILE;
+
+/// Upstream repository publishing `shebe-mcp` releases.
+pub const DEFAULT_REPO: &str = "rhobimd-oss/shebe";
+
+/// Origin of release metadata and assets.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ReleaseSource {
+    /// GitHub releases of `owner/repo`.
+    GitHub { repo: String },
+    /// A static mirror described by the manifest at
+    /// `manifest_url`.
+    Mirror { manifest_url: String },
+}
+
+impl Default for ReleaseSource {
+    fn default() -> Self {
+        Self::GitHub {
+            repo: DEFAULT_REPO.to_string(),
+        }
+    }
+}
+
+impl FromStr for ReleaseSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.starts_with("https://") || s.starts_with("http://") {
+            let manifest_url = if s.ends_with(".json") {
+                s.to_string()
+            } else {
+                format!(
+                    "{}/{}",
+                    s.trim_end_matches('/'),
+                    MANIFEST_FILE,
+                )
+            };
+            return Ok(Self::Mirror { manifest_url });
+        }
+
+        let valid = s.split_once('/').is_some_and(|(owner, repo)| {
+            !owner.is_empty()
+                && !repo.is_empty()
+                && !repo.contains('/')
+                && !s.contains(char::is_whitespace)
+        });
+        if !valid {
+            return Err(format!(
+                "invalid release_source '{}': expected a GitHub \
+                 \"owner/repo\" or an http(s) mirror or \
+                 manifest URL",
+                s,
+            ));
+        }
+        Ok(Self::GitHub {
+            repo: s.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for ReleaseSource {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for ReleaseSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GitHub { repo } => f.write_str(repo),
+            Self::Mirror { manifest_url } => {
+                f.write_str(manifest_url)
+            }
+        }
+    }
+}
+
+impl ReleaseSource {
+    /// URL of the mirror manifest, `None` for GitHub.
+    pub fn manifest_url(&self) -> Option<&str> {
+        match self {
+            Self::GitHub { .. } => None,
+            Self::Mirror { manifest_url } => Some(manifest_url),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_github_repo() {
+        assert_eq!(
+            "acme/shebe-fork".parse(),
+            Ok(ReleaseSource::GitHub {
+                repo: "acme/shebe-fork".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn parses_mirror_url() {
+        let source: ReleaseSource =
+            "https://mirror.example.com/shebe/".parse().unwrap();
+        assert_eq!(
+            source.manifest_url(),
+            Some("https://mirror.example.com/shebe/releases.json"),
+        );
+    }
+
+    #[test]
+    fn parses_manifest_url() {
+        let source: ReleaseSource =
+            "https://mirror.example.com/shebe/stable.json"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            source,
+            ReleaseSource::Mirror {
+                manifest_url: "https://mirror.example.com/shebe/\
+                               stable.json"
+                    .into(),
+            },
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_sources() {
+        for s in ["", "shebe", "/shebe", "acme/", "a/b/c", "a b/c"] {
+            assert!(
+                s.parse::<ReleaseSource>().is_err(),
+                "accepted '{}'",
+                s,
+            );
+        }
+    }
+
+    #[test]
+    fn default_is_upstream() {
+        let source = ReleaseSource::default();
+        assert_eq!(source.to_string(), DEFAULT_REPO);
+        assert_eq!(source.manifest_url(), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Persistent extension state in `state.json` in the work
+//! dir.
+//!
+//! Extension instances do not survive a Zed restart, so
+//! anything that has to outlive one (which install is
+//! active and which one to roll back to, when releases
+//! were last checked, what the user should be told about
+//! it, which symbols `/shebe-refs` looked up) lives here. A
+//! missing or unreadable file is treated as empty state.
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use zed_extension_api::serde_json;
+
+use crate::platform::Libc;
+
+/// State file name, relative to the work dir.
+pub const STATE_FILE: &str = "state.json";
+
+/// How many recently searched symbols are remembered for
+/// argument completion.
+pub const RECENT_SYMBOLS: usize = 10;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// The managed binary in use.
+    pub active: Option<Installed>,
+    /// The managed binary that was active before `active`,
+    /// kept for `version: "previous"` and automatic rollback.
+    pub previous: Option<Installed>,
+    /// Unix time of the last successful release lookup.
+    pub last_update_check: Option<u64>,
+    /// Outcome of the last update check worth showing to the
+    /// user, e.g. an update that was applied.
+    pub notice: Option<String>,
+    /// Symbols recently passed to `/shebe-refs`, newest
+    /// first.
+    pub recent_symbols: Vec<String>,
+}
+
+/// A managed install recorded in the state file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Installed {
+    /// Install dir, e.g. `shebe-v0.5.7`.
+    pub dir: String,
+    /// Linux build flavour it was installed with.
+    pub libc: Libc,
+    /// `release_source` it was downloaded from, so a fork's
+    /// build is never mistaken for an upstream one with the
+    /// same tag.
+    #[serde(default)]
+    pub source: String,
+}
+
+impl State {
+    pub fn load() -> Self {
+        fs::read_to_string(STATE_FILE)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the state back. Failures are logged only: losing
+    /// the state costs an extra release lookup, nothing more.
+    pub fn save(&self) {
+        let written = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                fs::write(STATE_FILE, json)
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = written {
+            eprintln!("shebe: failed to write {}: {}", STATE_FILE, e);
+        }
+    }
+
+    /// Record `symbol` as the most recent lookup, keeping at
+    /// most [`RECENT_SYMBOLS`].
+    pub fn remember_symbol(&mut self, symbol: &str) {
+        self.recent_symbols.retain(|s| s != symbol);
+        self.recent_symbols.insert(0, symbol.to_string());
+        self.recent_symbols.truncate(RECENT_SYMBOLS);
+    }
+
+    /// Whether a release lookup is due, `interval_hours`
+    /// after the last one. An interval of 0 disables
+    /// periodic checks.
+    pub fn update_check_due(&self, interval_hours: u64) -> bool {
+        match self.last_update_check {
+            None => true,
+            Some(_) if interval_hours == 0 => false,
+            Some(last) => {
+                now().saturating_sub(last)
+                    >= interval_hours.saturating_mul(3600)
+            }
+        }
+    }
+}
+
+/// Current Unix time in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -8,7 +8,7 @@
 //! All tests are `#[ignore]` by default (require network).
 //! Run with: `cargo test -- --ignored`
 
-use std::io::Write;
+use std::io::{BufRead, BufReader, Write};
 use std::os::unix::fs::PermissionsExt;
 use std::process::{Command, Stdio};
 use std::sync::OnceLock;
@@ -16,7 +16,11 @@ use std::sync::OnceLock;
 use flate2::read::GzDecoder;
 use reqwest::blocking::Client;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use tempfile::TempDir;
+use zed_shebe::mcp::{self, Transport};
+use zed_shebe::platform::{self, Arch, Libc, Os, Platform};
+use zed_shebe::source::ReleaseSource;
 
 // -- GitHub API types -------------------------------------------
 
@@ -34,14 +38,36 @@ struct Asset {
 
 // -- Helpers ----------------------------------------------------
 
-const REPO: &str = "rhobimd-oss/shebe";
 const API_BASE: &str = "https://api.github.com";
 
-/// Supported platform tuples: (os_str, arch_str).
-const SUPPORTED_PLATFORMS: &[(&str, &str)] = &[
-    ("darwin", "aarch64"),
-    ("darwin", "x86_64"),
-    ("linux", "x86_64"),
+/// Repository under test: `SHEBE_RELEASE_SOURCE` if set,
+/// parsed like the extension's `release_source` setting,
+/// else the extension's default. Only GitHub sources can be
+/// exercised here.
+fn repo() -> String {
+    let source = match std::env::var("SHEBE_RELEASE_SOURCE") {
+        Ok(s) if !s.is_empty() => s
+            .parse::<ReleaseSource>()
+            .unwrap_or_else(|e| panic!("{}", e)),
+        _ => ReleaseSource::default(),
+    };
+    match source {
+        ReleaseSource::GitHub { repo } => repo,
+        ReleaseSource::Mirror { manifest_url } => panic!(
+            "SHEBE_RELEASE_SOURCE must be a GitHub owner/repo, \
+             got manifest {}",
+            manifest_url,
+        ),
+    }
+}
+
+/// Supported platform tuples. Linux aarch64 is skipped for
+/// releases that predate it, see [`is_newer_platform`].
+const SUPPORTED_PLATFORMS: &[(Os, Arch)] = &[
+    (Os::Darwin, Arch::Aarch64),
+    (Os::Darwin, Arch::X86_64),
+    (Os::Linux, Arch::X86_64),
+    (Os::Linux, Arch::Aarch64),
 ];
 
 fn github_client() -> Client {
@@ -67,7 +93,8 @@ fn github_client() -> Client {
 fn fetch_latest_release(client: &Client) -> Release {
     let url = format!(
         "{}/repos/{}/releases/latest",
-        API_BASE, REPO,
+        API_BASE,
+        repo(),
     );
     let resp = client.get(&url).send().unwrap();
     assert!(
@@ -94,18 +121,73 @@ fn cached_release() -> Release {
         .clone()
 }
 
-/// Build asset name the same way the extension does.
+/// Supported glibc platform tuples. These are the builds
+/// the Homebrew formula installs and that `libc: "gnu"` (or
+/// `"auto"` on a glibc host) selects.
+const SUPPORTED_GNU_PLATFORMS: &[(Os, Arch)] = &[
+    (Os::Linux, Arch::X86_64),
+];
+
+/// Build asset name with the extension's own resolver.
 /// Linux uses the musl variant for portability.
-fn expected_asset_name(
+fn expected_asset_name(version: &str, os: Os, arch: Arch) -> String {
+    expected_asset_name_for_libc(version, os, arch, Libc::Musl)
+}
+
+/// Platforms upstream only started building in a later
+/// release. Older releases lack their assets, and tests
+/// skip them with a message instead of failing.
+fn is_newer_platform(os: Os, arch: Arch) -> bool {
+    (os, arch) == (Os::Linux, Arch::Aarch64)
+}
+
+/// The musl (Linux) or only (macOS) archive for `os`/`arch`
+/// in `release`. Panics if it is missing, unless the
+/// platform [`is_newer_platform`], in which case the skip is
+/// logged and `None` returned.
+fn platform_asset(release: &Release, os: Os, arch: Arch) -> Option<Asset> {
+    let name = expected_asset_name(&release.tag_name, os, arch);
+    let asset = release.assets.iter().find(|a| a.name == name);
+    if asset.is_none() && is_newer_platform(os, arch) {
+        eprintln!(
+            "skipping: release {} has no '{}' yet",
+            release.tag_name, name,
+        );
+        return None;
+    }
+    Some(
+        asset
+            .unwrap_or_else(|| {
+                panic!("asset '{}' not found in release", name)
+            })
+            .clone(),
+    )
+}
+
+/// Build asset name for an explicit Linux libc flavour.
+/// Ignored on macOS.
+fn expected_asset_name_for_libc(
     version: &str,
-    os: &str,
-    arch: &str,
+    os: Os,
+    arch: Arch,
+    libc: Libc,
 ) -> String {
-    let suffix = if os == "linux" { "-musl" } else { "" };
-    format!(
-        "shebe-{}-{}-{}{}.tar.gz",
-        version, os, arch, suffix,
-    )
+    let platform = Platform {
+        os,
+        arch,
+        glibc: false,
+    };
+    platform::candidate_names(version, platform, libc)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .remove(0)
+}
+
+/// Lowercase hex SHA256 digest of `bytes`.
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
 }
 
 /// Download and extract an asset into a temp dir.
@@ -136,22 +218,16 @@ fn download_and_extract(
 }
 
 /// Download the current-platform binary, make it executable
-/// and return (temp_dir, binary_path).
-fn download_current_platform_binary() -> (TempDir, std::path::PathBuf) {
+/// and return (temp_dir, binary_path). `None` if the release
+/// has no build for this platform yet, see
+/// [`platform_asset`].
+fn download_current_platform_binary(
+) -> Option<(TempDir, std::path::PathBuf)> {
     let client = github_client();
     let release = cached_release();
     let (os, arch) = current_platform();
-    let name = expected_asset_name(
-        &release.tag_name, os, arch,
-    );
-    let asset = release
-        .assets
-        .iter()
-        .find(|a| a.name == name)
-        .unwrap_or_else(|| {
-            panic!("asset '{}' not found in release", name)
-        });
-    let (tmp, binary) = download_and_extract(&client, asset);
+    let asset = platform_asset(&release, os, arch)?;
+    let (tmp, binary) = download_and_extract(&client, &asset);
 
     #[cfg(unix)]
     {
@@ -161,105 +237,79 @@ fn download_current_platform_binary() -> (TempDir, std::path::PathBuf) {
         std::fs::set_permissions(&binary, perms).unwrap();
     }
 
-    (tmp, binary)
+    Some((tmp, binary))
 }
 
-/// A running shebe-mcp process with stdin/stdout handles.
-struct McpProcess {
+/// The extension's MCP transport over a running
+/// shebe-mcp's stdin/stdout.
+struct StdioTransport {
     child: std::process::Child,
-    reader: std::io::BufReader<std::process::ChildStdout>,
-    next_id: u64,
-}
-
-impl McpProcess {
-    /// Spawn shebe-mcp binary and send the initialize
-    /// handshake. Returns a ready-to-use McpProcess.
-    fn spawn_and_initialize(
-        binary: &std::path::Path,
-    ) -> Self {
-        let mut child = Command::new(binary)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .unwrap_or_else(|e| {
-                panic!("failed to spawn shebe-mcp: {}", e)
-            });
-
-        let stdout = child.stdout.take().unwrap();
-        let reader = std::io::BufReader::new(stdout);
-        let mut proc = Self { child, reader, next_id: 1 };
-
-        let _init_resp = proc.send_request(
-            "initialize",
-            serde_json::json!({
-                "protocolVersion": "2024-11-05",
-                "capabilities": {},
-                "clientInfo": {
-                    "name": "integration-test",
-                    "version": "0.0.1"
-                }
-            }),
-        );
+    stdin: std::process::ChildStdin,
+    reader: BufReader<std::process::ChildStdout>,
+}
 
-        proc
+impl Transport for StdioTransport {
+    fn send(&mut self, message: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", message)
+            .and_then(|()| self.stdin.flush())
+            .map_err(|e| e.to_string())
     }
 
-    /// Send a JSON-RPC request and read one line response.
-    fn send_request(
-        &mut self,
-        method: &str,
-        params: serde_json::Value,
-    ) -> serde_json::Value {
-        let id = self.next_id;
-        self.next_id += 1;
-
-        let request = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": id,
-            "method": method,
-            "params": params,
-        });
-        let body = serde_json::to_string(&request).unwrap();
-
-        let stdin = self.child.stdin.as_mut().unwrap();
-        stdin.write_all(body.as_bytes()).unwrap();
-        stdin.write_all(b"\n").unwrap();
-        stdin.flush().unwrap();
-
+    fn receive(&mut self) -> Result<Option<String>, String> {
         let mut line = String::new();
-        std::io::BufRead::read_line(
-            &mut self.reader, &mut line,
-        )
-        .expect("failed to read response line");
-
-        serde_json::from_str(line.trim()).unwrap()
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(line)),
+            Err(e) => Err(e.to_string()),
+        }
     }
 }
 
-impl Drop for McpProcess {
+impl Drop for StdioTransport {
     fn drop(&mut self) {
         let _ = self.child.kill();
         let _ = self.child.wait();
     }
 }
 
-/// Return the current platform tuple (os_str, arch_str)
-/// for the machine running the tests so we can pick
-/// which binary to actually execute.
-fn current_platform() -> (&'static str, &'static str) {
+/// Spawn the shebe-mcp binary and start an MCP session
+/// with the extension's own client.
+fn spawn_client(
+    binary: &std::path::Path,
+) -> mcp::Client<StdioTransport> {
+    let mut child = Command::new(binary)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap_or_else(|e| {
+            panic!("failed to spawn shebe-mcp: {}", e)
+        });
+
+    let transport = StdioTransport {
+        stdin: child.stdin.take().unwrap(),
+        reader: BufReader::new(child.stdout.take().unwrap()),
+        child,
+    };
+    mcp::Client::connect(transport).unwrap()
+}
+
+/// Return the current platform tuple for the machine
+/// running the tests so we can pick which binary to
+/// actually execute.
+fn current_platform() -> (Os, Arch) {
     let os = if cfg!(target_os = "macos") {
-        "darwin"
+        Os::Darwin
     } else if cfg!(target_os = "linux") {
-        "linux"
+        Os::Linux
     } else {
         panic!("unsupported test runner OS");
     };
 
     let arch = if cfg!(target_arch = "aarch64") {
-        "aarch64"
+        Arch::Aarch64
     } else if cfg!(target_arch = "x86_64") {
-        "x86_64"
+        Arch::X86_64
     } else {
         panic!("unsupported test runner arch");
     };
@@ -290,7 +340,7 @@ fn darwin_aarch64_asset_downloads() {
     let client = github_client();
     let release = cached_release();
     let name = expected_asset_name(
-        &release.tag_name, "darwin", "aarch64",
+        &release.tag_name, Os::Darwin, Arch::Aarch64,
     );
     let asset = release
         .assets
@@ -313,7 +363,7 @@ fn darwin_x86_64_asset_downloads() {
     let client = github_client();
     let release = cached_release();
     let name = expected_asset_name(
-        &release.tag_name, "darwin", "x86_64",
+        &release.tag_name, Os::Darwin, Arch::X86_64,
     );
     let asset = release
         .assets
@@ -336,7 +386,7 @@ fn linux_x86_64_asset_downloads() {
     let client = github_client();
     let release = cached_release();
     let name = expected_asset_name(
-        &release.tag_name, "linux", "x86_64",
+        &release.tag_name, Os::Linux, Arch::X86_64,
     );
     let asset = release
         .assets
@@ -359,17 +409,10 @@ fn extracted_binary_is_executable() {
     let client = github_client();
     let release = cached_release();
     let (os, arch) = current_platform();
-    let name = expected_asset_name(
-        &release.tag_name, os, arch,
-    );
-    let asset = release
-        .assets
-        .iter()
-        .find(|a| a.name == name)
-        .unwrap_or_else(|| {
-            panic!("asset '{}' not found in release", name)
-        });
-    let (_tmp, binary) = download_and_extract(&client, asset);
+    let Some(asset) = platform_asset(&release, os, arch) else {
+        return;
+    };
+    let (_tmp, binary) = download_and_extract(&client, &asset);
     let perms = std::fs::metadata(&binary).unwrap().permissions();
     assert!(
         perms.mode() & 0o111 != 0,
@@ -382,17 +425,19 @@ fn extracted_binary_is_executable() {
 #[test]
 #[ignore]
 fn binary_responds_to_jsonrpc_initialize() {
-    let (_tmp, binary) =
-        download_current_platform_binary();
-    let mcp = McpProcess::spawn_and_initialize(&binary);
-
-    // The initialize response was already consumed by
-    // spawn_and_initialize; if we got here without panic
-    // the binary accepted the handshake. Verify the
-    // process is still alive.
+    let Some((_tmp, binary)) = download_current_platform_binary()
+    else {
+        return;
+    };
+    let mut mcp = spawn_client(&binary);
+
+    let info = mcp
+        .server_info()
+        .unwrap_or_else(|e| panic!("initialize failed: {}", e));
     assert!(
-        mcp.child.id() > 0,
-        "shebe-mcp process is not running",
+        info["serverInfo"]["name"].is_string(),
+        "initialize result has no serverInfo: {}",
+        info,
     );
 }
 
@@ -400,23 +445,15 @@ fn binary_responds_to_jsonrpc_initialize() {
 #[test]
 #[ignore]
 fn tools_list_contains_expected_tools() {
-    let (_tmp, binary) =
-        download_current_platform_binary();
-    let mut mcp = McpProcess::spawn_and_initialize(&binary);
-
-    let response = mcp.send_request(
-        "tools/list",
-        serde_json::json!({}),
-    );
-
-    let tools = response["result"]["tools"]
-        .as_array()
-        .expect("result.tools is not an array");
+    let Some((_tmp, binary)) = download_current_platform_binary()
+    else {
+        return;
+    };
+    let mut mcp = spawn_client(&binary);
 
-    let tool_names: Vec<&str> = tools
-        .iter()
-        .filter_map(|t| t["name"].as_str())
-        .collect();
+    let tool_names = mcp
+        .list_tools()
+        .unwrap_or_else(|e| panic!("tools/list failed: {}", e));
 
     let expected = [
         "index_repository",
@@ -428,7 +465,7 @@ fn tools_list_contains_expected_tools() {
 
     for name in &expected {
         assert!(
-            tool_names.contains(name),
+            tool_names.iter().any(|t| t == name),
             "expected tool '{}' not found; available: {:?}",
             name,
             tool_names,
@@ -436,6 +473,72 @@ fn tools_list_contains_expected_tools() {
     }
 }
 
+/// T1.8 -- Archive matches its published .sha256 sidecar.
+#[test]
+#[ignore]
+fn archive_matches_sha256_sidecar() {
+    let client = github_client();
+    let release = cached_release();
+    let (os, arch) = current_platform();
+    let Some(archive) = platform_asset(&release, os, arch) else {
+        return;
+    };
+    let find = |wanted: &str| {
+        release
+            .assets
+            .iter()
+            .find(|a| a.name == wanted)
+            .unwrap_or_else(|| {
+                panic!("asset '{}' not found in release", wanted)
+            })
+            .clone()
+    };
+    let sidecar = find(&format!("{}.sha256", archive.name));
+
+    let archive_bytes = client
+        .get(&archive.browser_download_url)
+        .send()
+        .unwrap()
+        .bytes()
+        .unwrap();
+    let sidecar_text = client
+        .get(&sidecar.browser_download_url)
+        .send()
+        .unwrap()
+        .text()
+        .unwrap();
+
+    let expected = sidecar_text
+        .split_whitespace()
+        .next()
+        .expect("sidecar is empty")
+        .to_ascii_lowercase();
+    assert_eq!(
+        sha256_hex(&archive_bytes),
+        expected,
+        "SHA256 of '{}' does not match its sidecar",
+        archive.name,
+    );
+}
+
+/// T1.9 -- linux-aarch64 asset exists and downloads.
+#[test]
+#[ignore]
+fn linux_aarch64_asset_downloads() {
+    let client = github_client();
+    let release = cached_release();
+    let Some(asset) =
+        platform_asset(&release, Os::Linux, Arch::Aarch64)
+    else {
+        return;
+    };
+    let (_tmp, binary) = download_and_extract(&client, &asset);
+    assert!(
+        binary.exists(),
+        "shebe-mcp not found after extraction",
+    );
+}
+
 // ===============================================================
 // Layer 2: Boundary (Edge Cases)
 // ===============================================================
@@ -449,10 +552,19 @@ fn asset_names_match_extension_logic() {
     let asset_names: Vec<&str> =
         release.assets.iter().map(|a| a.name.as_str()).collect();
 
-    for (os, arch) in SUPPORTED_PLATFORMS {
+    for &(os, arch) in SUPPORTED_PLATFORMS {
         let expected = expected_asset_name(
             &release.tag_name, os, arch,
         );
+        if is_newer_platform(os, arch)
+            && !asset_names.contains(&expected.as_str())
+        {
+            eprintln!(
+                "skipping: release {} has no '{}' yet",
+                release.tag_name, expected,
+            );
+            continue;
+        }
         assert!(
             asset_names.contains(&expected.as_str()),
             "expected asset '{}' not found; available: {:?}",
@@ -478,18 +590,35 @@ fn no_windows_asset() {
     );
 }
 
-/// T2.3 -- No Linux ARM asset exists.
+/// T2.3 -- Linux ARM binary is an aarch64 ELF executable.
+/// Guards against a mislabelled x86_64 build being served to
+/// ARM machines now that the extension resolves this asset.
 #[test]
 #[ignore]
-fn no_linux_arm_asset() {
+fn linux_arm_binary_is_aarch64_elf() {
+    let client = github_client();
     let release = cached_release();
-    let linux_arm = release.assets.iter().find(|a| {
-        a.name.contains("linux") && a.name.contains("aarch64")
-    });
+    let Some(asset) =
+        platform_asset(&release, Os::Linux, Arch::Aarch64)
+    else {
+        return;
+    };
+    let name = asset.name.clone();
+    let (_tmp, binary) = download_and_extract(&client, &asset);
+    let bytes = std::fs::read(&binary).unwrap();
+
+    // ELF magic, then e_machine (little-endian u16) at
+    // offset 18; EM_AARCH64 is 183.
     assert!(
-        linux_arm.is_none(),
-        "unexpected linux-aarch64 asset found: {}",
-        linux_arm.map(|a| &a.name).unwrap_or(&String::new()),
+        bytes.len() > 20 && bytes.starts_with(b"\x7fELF"),
+        "shebe-mcp in '{}' is not an ELF binary",
+        name,
+    );
+    let machine = u16::from_le_bytes([bytes[18], bytes[19]]);
+    assert_eq!(
+        machine, 183,
+        "shebe-mcp in '{}' is not an aarch64 binary",
+        name,
     );
 }
 
@@ -521,16 +650,9 @@ fn archive_contains_binary_at_root() {
     let client = github_client();
     let release = cached_release();
     let (os, arch) = current_platform();
-    let name = expected_asset_name(
-        &release.tag_name, os, arch,
-    );
-    let asset = release
-        .assets
-        .iter()
-        .find(|a| a.name == name)
-        .unwrap_or_else(|| {
-            panic!("asset '{}' not found in release", name)
-        });
+    let Some(asset) = platform_asset(&release, os, arch) else {
+        return;
+    };
 
     let resp = client
         .get(&asset.browser_download_url)
@@ -558,6 +680,60 @@ fn archive_contains_binary_at_root() {
     );
 }
 
+/// T2.6 -- Every tarball has a .sha256 sidecar. The
+/// extension refuses to install an archive without one.
+#[test]
+#[ignore]
+fn every_archive_has_sha256_sidecar() {
+    let release = cached_release();
+    let asset_names: Vec<&str> =
+        release.assets.iter().map(|a| a.name.as_str()).collect();
+
+    for &(os, arch) in SUPPORTED_PLATFORMS {
+        let archive = expected_asset_name(&release.tag_name, os, arch);
+        if is_newer_platform(os, arch)
+            && !asset_names.contains(&archive.as_str())
+        {
+            continue;
+        }
+        let sidecar = format!("{}.sha256", archive);
+        assert!(
+            asset_names.contains(&sidecar.as_str()),
+            "expected checksum '{}' not found; available: {:?}",
+            sidecar,
+            asset_names,
+        );
+    }
+}
+
+/// T2.7 -- glibc asset naming matches extension logic. The
+/// glibc build has no libc suffix, unlike the musl build.
+#[test]
+#[ignore]
+fn gnu_asset_names_match_extension_logic() {
+    let release = cached_release();
+    let asset_names: Vec<&str> =
+        release.assets.iter().map(|a| a.name.as_str()).collect();
+
+    for &(os, arch) in SUPPORTED_GNU_PLATFORMS {
+        let expected = expected_asset_name_for_libc(
+            &release.tag_name, os, arch, Libc::Gnu,
+        );
+        assert!(
+            asset_names.contains(&expected.as_str()),
+            "expected asset '{}' not found; available: {:?}",
+            expected,
+            asset_names,
+        );
+        assert!(
+            asset_names
+                .contains(&format!("{}.sha256", expected).as_str()),
+            "expected checksum for '{}' not found",
+            expected,
+        );
+    }
+}
+
 // ===============================================================
 // Layer 3: Beyond Boundary (Failure Modes)
 // ===============================================================
@@ -590,7 +766,7 @@ fn nonexistent_asset_url_returns_error() {
     let url = format!(
         "https://github.com/{}/releases/download/\
          v0.0.0-fake/shebe-v0.0.0-fake-linux-x86_64.tar.gz",
-        REPO,
+        repo(),
     );
     let resp = client.get(&url).send().unwrap();
     assert!(
@@ -607,16 +783,9 @@ fn truncated_archive_fails_extraction() {
     let client = github_client();
     let release = cached_release();
     let (os, arch) = current_platform();
-    let name = expected_asset_name(
-        &release.tag_name, os, arch,
-    );
-    let asset = release
-        .assets
-        .iter()
-        .find(|a| a.name == name)
-        .unwrap_or_else(|| {
-            panic!("asset '{}' not found in release", name)
-        });
+    let Some(asset) = platform_asset(&release, os, arch) else {
+        return;
+    };
 
     let resp = client
         .get(&asset.browser_download_url)
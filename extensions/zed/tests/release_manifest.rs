@@ -0,0 +1,299 @@
+//! Integration tests for static release mirrors.
+//!
+//! A local HTTP stand-in serves a `releases.json` manifest
+//! and release archives the way a mirror would, and the
+//! tests walk the same steps as the extension: fetch and
+//! parse the manifest, pick a release with the extension's
+//! resolver, resolve the asset for a platform, download it
+//! and verify its digest. Only the HTTP fetch, which goes
+//! through Zed's host in the extension, is done here.
+//!
+//! No network access is needed, so these run by default.
+
+use std::collections::HashMap;
+use std::thread;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use reqwest::blocking::Client;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use tiny_http::{Response, Server};
+use zed_shebe::checksum;
+use zed_shebe::error::ShebeError;
+use zed_shebe::manifest::{
+    asset_name, Manifest, ManifestRelease, Target, MANIFEST_FILE,
+    SCHEMA_VERSION,
+};
+use zed_shebe::platform::{self, Arch, Checksum, Libc, Os, Platform};
+use zed_shebe::release::{
+    self, Channel, ReleaseQuery, VersionSpec,
+};
+use zed_shebe::source::ReleaseSource;
+
+// -- Mirror stand-in --------------------------------------------
+
+/// Serve `files` (path -> body) on an ephemeral local port
+/// until the test process exits. Returns the base URL.
+fn serve(files: HashMap<String, Vec<u8>>) -> String {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = match files.get(request.url()) {
+                Some(body) => Response::from_data(body.clone()),
+                None => Response::from_data(Vec::new())
+                    .with_status_code(404),
+            };
+            let _ = request.respond(response);
+        }
+    });
+    format!("http://127.0.0.1:{}", port)
+}
+
+/// A release archive holding a stub `shebe-mcp`.
+fn archive() -> Vec<u8> {
+    let script = b"#!/bin/sh\necho shebe-mcp\n";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(script.len() as u64);
+    header.set_mode(0o755);
+    header.set_cksum();
+
+    let mut builder = tar::Builder::new(GzEncoder::new(
+        Vec::new(),
+        Compression::default(),
+    ));
+    builder
+        .append_data(&mut header, "shebe-mcp", &script[..])
+        .unwrap();
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+const TARGET: &str = "linux-x86_64-musl";
+
+const LINUX_X86_64: Platform = Platform {
+    os: Os::Linux,
+    arch: Arch::X86_64,
+    glibc: false,
+};
+
+fn release(version: &str, sha256: &str) -> ManifestRelease {
+    let name = asset_name(version, TARGET);
+    ManifestRelease {
+        version: version.into(),
+        prerelease: false,
+        min_extension_version: None,
+        targets: [(
+            TARGET.to_string(),
+            Target {
+                url: format!("{}/{}", version, name),
+                sha256: sha256.into(),
+            },
+        )]
+        .into(),
+    }
+}
+
+/// A mirror publishing `releases` with one archive each.
+/// Returns its `release_source` setting value.
+fn mirror(releases: Vec<ManifestRelease>) -> String {
+    let bytes = archive();
+    let mut files = HashMap::new();
+    for release in &releases {
+        let target = &release.targets[TARGET];
+        files.insert(format!("/shebe/{}", target.url), bytes.clone());
+    }
+    let manifest = Manifest {
+        schema_version: SCHEMA_VERSION,
+        releases,
+    };
+    files.insert(
+        format!("/shebe/{}", MANIFEST_FILE),
+        serde_json::to_vec(&manifest).unwrap(),
+    );
+    format!("{}/shebe", serve(files))
+}
+
+/// Check downloaded `bytes` against `expected` the way the
+/// extension checks an archive before extracting it.
+fn verify(bytes: &[u8], expected: &str) -> Result<(), ShebeError> {
+    let tmp = TempDir::new().unwrap();
+    let path = tmp.path().join("archive.tar.gz");
+    std::fs::write(&path, bytes).unwrap();
+    checksum::verify_digest(
+        path.to_str().unwrap(),
+        "archive.tar.gz",
+        expected,
+    )
+}
+
+fn fetch(client: &Client, url: &str) -> Vec<u8> {
+    let resp = client.get(url).send().unwrap();
+    assert!(
+        resp.status().is_success(),
+        "GET {} returned {}",
+        url,
+        resp.status(),
+    );
+    resp.bytes().unwrap().to_vec()
+}
+
+/// Fetch and parse the manifest of `source`.
+fn fetch_manifest(
+    client: &Client,
+    source: &ReleaseSource,
+) -> Result<Manifest, String> {
+    let url = source.manifest_url().expect("mirror source");
+    let body = fetch(client, url);
+    Manifest::parse(std::str::from_utf8(&body).unwrap())
+}
+
+// -- Tests ------------------------------------------------------
+
+#[test]
+fn installs_newest_supported_release_from_mirror() {
+    let digest = sha256_hex(&archive());
+    let mut too_new = release("v9.0.0", &digest);
+    too_new.min_extension_version = Some("99.0.0".into());
+    let source: ReleaseSource = mirror(vec![
+        too_new,
+        release("v0.5.7", &digest),
+        release("v0.5.6", &digest),
+    ])
+    .parse()
+    .unwrap();
+    let manifest_url = source.manifest_url().unwrap().to_string();
+    assert!(manifest_url.ends_with("/shebe/releases.json"));
+
+    let client = Client::new();
+    let manifest = fetch_manifest(&client, &source).unwrap();
+    let query = ReleaseQuery {
+        source: source.clone(),
+        ..ReleaseQuery::default()
+    };
+    let resolution =
+        release::select_from_manifest(&manifest, &manifest_url, &query)
+            .unwrap();
+    let chosen = resolution.release;
+    assert_eq!(chosen.version, "v0.5.7");
+    // v9.0.0 needs a newer extension, so it is not offered.
+    assert_eq!(resolution.newest.as_deref(), Some("v0.5.7"));
+
+    let resolved =
+        platform::resolve(&chosen, LINUX_X86_64, Libc::Auto).unwrap();
+    let Checksum::Sha256(expected) = resolved.checksum else {
+        panic!("expected an inline digest: {:?}", resolved.checksum);
+    };
+    let bytes = fetch(&client, &resolved.archive.download_url);
+    verify(&bytes, expected).unwrap();
+
+    let tmp = TempDir::new().unwrap();
+    tar::Archive::new(flate2::read::GzDecoder::new(&bytes[..]))
+        .unpack(tmp.path())
+        .unwrap();
+    assert!(tmp.path().join("shebe-mcp").is_file());
+}
+
+#[test]
+fn resolves_pins_and_channels_from_mirror() {
+    let digest = sha256_hex(&archive());
+    let mut rc = release("v0.6.0-rc.1", &digest);
+    rc.prerelease = true;
+    let source: ReleaseSource = mirror(vec![
+        rc,
+        release("v0.5.7", &digest),
+        release("v0.5.6", &digest),
+    ])
+    .parse()
+    .unwrap();
+    let manifest_url = source.manifest_url().unwrap().to_string();
+    let manifest = fetch_manifest(&Client::new(), &source).unwrap();
+
+    let pick = |version: &str, channel| {
+        let query = ReleaseQuery {
+            source: source.clone(),
+            version: version.parse::<VersionSpec>().unwrap(),
+            channel,
+            libc: Libc::Auto,
+        };
+        release::select_from_manifest(&manifest, &manifest_url, &query)
+            .map(|r| r.release.version)
+    };
+    assert_eq!(pick("latest", Channel::Stable).unwrap(), "v0.5.7");
+    assert_eq!(pick("v0.5.6", Channel::Stable).unwrap(), "v0.5.6");
+    assert_eq!(
+        pick("~0.6", Channel::Prerelease).unwrap(),
+        "v0.6.0-rc.1",
+    );
+    let err = pick("~0.6", Channel::Stable).unwrap_err();
+    assert!(err.contains("matches version '~0.6'"), "{}", err);
+}
+
+#[test]
+fn detects_tampered_archive() {
+    let source: ReleaseSource = mirror(vec![release(
+        "v0.5.7",
+        &"0".repeat(64),
+    )])
+    .parse()
+    .unwrap();
+
+    let client = Client::new();
+    let manifest = fetch_manifest(&client, &source).unwrap();
+    let release = manifest.releases[0]
+        .to_release(source.manifest_url().unwrap());
+    let resolved =
+        platform::resolve(&release, LINUX_X86_64, Libc::Musl).unwrap();
+    let Checksum::Sha256(expected) = resolved.checksum else {
+        panic!("expected an inline digest: {:?}", resolved.checksum);
+    };
+    let bytes = fetch(&client, &resolved.archive.download_url);
+    let err = verify(&bytes, expected).unwrap_err();
+    assert!(
+        matches!(err, ShebeError::ChecksumMismatch { .. }),
+        "{}",
+        err,
+    );
+}
+
+#[test]
+fn rejects_manifest_with_newer_schema() {
+    let mut files = HashMap::new();
+    files.insert(
+        "/stable.json".to_string(),
+        br#"{"schema_version": 99, "releases": []}"#.to_vec(),
+    );
+    let source: ReleaseSource =
+        format!("{}/stable.json", serve(files)).parse().unwrap();
+
+    let err = fetch_manifest(&Client::new(), &source).unwrap_err();
+    assert!(err.contains("schema_version 99"), "{}", err);
+}
+
+#[test]
+fn missing_target_reports_available_assets() {
+    let digest = sha256_hex(&archive());
+    let source: ReleaseSource =
+        mirror(vec![release("v0.5.7", &digest)]).parse().unwrap();
+
+    let manifest =
+        fetch_manifest(&Client::new(), &source).unwrap();
+    let release = manifest.releases[0]
+        .to_release(source.manifest_url().unwrap());
+    let darwin = Platform {
+        os: Os::Darwin,
+        arch: Arch::Aarch64,
+        glibc: false,
+    };
+    let err = platform::resolve(&release, darwin, Libc::Auto)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(&asset_name("v0.5.7", TARGET)), "{}", err);
+}
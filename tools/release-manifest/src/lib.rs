@@ -0,0 +1,341 @@
+//! Build a `releases.json` manifest from release artifacts.
+//!
+//! Expects one directory per release tag, laid out the way a
+//! static mirror serves them:
+//!
+//! ```text
+//! <artifacts>/v0.5.7/shebe-v0.5.7-darwin-aarch64.tar.gz
+//! <artifacts>/v0.5.7/shebe-v0.5.7-darwin-aarch64.tar.gz.sha256
+//! <artifacts>/v0.5.7/shebe-v0.5.7-linux-x86_64-musl.tar.gz
+//! <artifacts>/v0.6.0-rc.1/...
+//! ```
+//!
+//! Every archive must have a `.sha256` sidecar next to it,
+//! as on GitHub releases, and its digest must agree with
+//! the archive's. Both are checked with the extension's own
+//! `zed_shebe::checksum`, and the format itself is defined
+//! by `zed_shebe::manifest`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use semver::Version;
+use zed_shebe::checksum;
+use zed_shebe::manifest::{
+    target_of, Manifest, ManifestRelease, Target, SCHEMA_VERSION,
+};
+
+/// Generator settings.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Prefix for asset URLs. Without one, URLs are relative
+    /// to the manifest (`v0.5.7/shebe-...tar.gz`).
+    pub base_url: Option<String>,
+    /// Stamped on every release as `min_extension_version`.
+    pub min_extension_version: Option<Version>,
+}
+
+/// Scan `artifacts` and build the manifest, newest release
+/// first.
+pub fn generate(
+    artifacts: &Path,
+    options: &Options,
+) -> Result<Manifest, String> {
+    let entries = fs::read_dir(artifacts).map_err(|e| {
+        format!("failed to read {}: {}", artifacts.display(), e)
+    })?;
+
+    let mut releases = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        let Some(tag) = entry.file_name().to_str().map(String::from)
+        else {
+            continue;
+        };
+        let Ok(version) = Version::parse(tag.trim_start_matches('v'))
+        else {
+            continue;
+        };
+        if !path.is_dir() {
+            continue;
+        }
+
+        let targets = scan_release(&path, &tag, options)?;
+        if targets.is_empty() {
+            return Err(format!(
+                "{} contains no shebe-{}-*.tar.gz archives",
+                path.display(),
+                tag,
+            ));
+        }
+        releases.push((
+            version.clone(),
+            ManifestRelease {
+                version: tag,
+                prerelease: !version.pre.is_empty(),
+                min_extension_version: options
+                    .min_extension_version
+                    .as_ref()
+                    .map(Version::to_string),
+                targets,
+            },
+        ));
+    }
+
+    releases.sort_by(|(a, _), (b, _)| b.cmp(a));
+    Ok(Manifest {
+        schema_version: SCHEMA_VERSION,
+        releases: releases.into_iter().map(|(_, r)| r).collect(),
+    })
+}
+
+/// Hash every archive of release `tag` in `dir`.
+fn scan_release(
+    dir: &Path,
+    tag: &str,
+    options: &Options,
+) -> Result<BTreeMap<String, Target>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        format!("failed to read {}: {}", dir.display(), e)
+    })?;
+
+    let mut targets = BTreeMap::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let file_name = entry.file_name();
+        let Some(name) = file_name.to_str() else {
+            continue;
+        };
+        let Some(target) = target_of(tag, name) else {
+            continue;
+        };
+
+        let path = entry.path();
+        let sha256 = path
+            .to_str()
+            .ok_or_else(|| {
+                format!("{} is not valid UTF-8", path.display())
+            })
+            .and_then(|p| {
+                checksum::sha256_file(p).map_err(|e| {
+                    format!("failed to hash {}: {}", path.display(), e)
+                })
+            })?;
+        check_sidecar(&path, name, &sha256)?;
+
+        let relative = format!("{}/{}", tag, name);
+        let url = match &options.base_url {
+            Some(base) => {
+                format!("{}/{}", base.trim_end_matches('/'), relative)
+            }
+            None => relative,
+        };
+        targets.insert(target.to_string(), Target { url, sha256 });
+    }
+    Ok(targets)
+}
+
+/// Compare `sha256` with the archive's `.sha256` sidecar,
+/// parsed by the same rules the extension applies before
+/// installing.
+fn check_sidecar(
+    archive: &Path,
+    name: &str,
+    sha256: &str,
+) -> Result<(), String> {
+    let sidecar = archive.with_file_name(format!("{}.sha256", name));
+    let contents = fs::read_to_string(&sidecar).map_err(|e| {
+        format!("failed to read {}: {}", sidecar.display(), e)
+    })?;
+    let expected = checksum::parse_sidecar(&contents, name)
+        .map_err(|e| format!("{}: {}", sidecar.display(), e))?;
+    if expected != sha256 {
+        return Err(format!(
+            "{} does not match {}: sidecar has {}, archive hashes \
+             to {}",
+            sidecar.display(),
+            name,
+            expected,
+            sha256,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    /// SHA256 of `b"test"`.
+    const TEST_DIGEST: &str =
+        "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    fn write(dir: &Path, path: &str, contents: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn artifacts() -> TempDir {
+        let tmp = TempDir::new().unwrap();
+        for tag in ["v0.5.7", "v0.6.0-rc.1"] {
+            for target in ["darwin-aarch64", "linux-x86_64-musl"] {
+                let archive =
+                    format!("{0}/shebe-{0}-{1}.tar.gz", tag, target);
+                write(tmp.path(), &archive, "test");
+                write(
+                    tmp.path(),
+                    &format!("{}.sha256", archive),
+                    TEST_DIGEST,
+                );
+            }
+        }
+        write(tmp.path(), "README.md", "not a release");
+        tmp
+    }
+
+    #[test]
+    fn lists_releases_newest_first() {
+        let tmp = artifacts();
+        let manifest =
+            generate(tmp.path(), &Options::default()).unwrap();
+
+        assert_eq!(manifest.schema_version, SCHEMA_VERSION);
+        let versions: Vec<_> = manifest
+            .releases
+            .iter()
+            .map(|r| (r.version.as_str(), r.prerelease))
+            .collect();
+        assert_eq!(versions, [("v0.6.0-rc.1", true), ("v0.5.7", false)]);
+    }
+
+    #[test]
+    fn hashes_archives_with_relative_urls() {
+        let tmp = artifacts();
+        let manifest =
+            generate(tmp.path(), &Options::default()).unwrap();
+        let release = &manifest.releases[1];
+
+        assert_eq!(
+            release.targets.keys().collect::<Vec<_>>(),
+            ["darwin-aarch64", "linux-x86_64-musl"],
+        );
+        assert_eq!(
+            release.targets["darwin-aarch64"],
+            Target {
+                url: "v0.5.7/shebe-v0.5.7-darwin-aarch64.tar.gz".into(),
+                sha256: TEST_DIGEST.into(),
+            },
+        );
+    }
+
+    #[test]
+    fn applies_base_url_and_min_extension_version() {
+        let tmp = artifacts();
+        let options = Options {
+            base_url: Some("https://mirror.example.com/shebe/".into()),
+            min_extension_version: Some(Version::new(0, 5, 7)),
+        };
+        let manifest = generate(tmp.path(), &options).unwrap();
+        let release = &manifest.releases[1];
+
+        assert_eq!(release.min_extension_version.as_deref(), Some("0.5.7"));
+        assert_eq!(
+            release.targets["linux-x86_64-musl"].url,
+            "https://mirror.example.com/shebe/v0.5.7/\
+             shebe-v0.5.7-linux-x86_64-musl.tar.gz",
+        );
+    }
+
+    #[test]
+    fn output_parses_as_a_manifest() {
+        let tmp = artifacts();
+        let manifest =
+            generate(tmp.path(), &Options::default()).unwrap();
+        let json = serde_json::to_string_pretty(&manifest).unwrap();
+        assert_eq!(Manifest::parse(&json).unwrap(), manifest);
+    }
+
+    #[test]
+    fn accepts_matching_sidecar() {
+        let tmp = artifacts();
+        write(
+            tmp.path(),
+            "v0.5.7/shebe-v0.5.7-darwin-aarch64.tar.gz.sha256",
+            &format!(
+                "{}  shebe-v0.5.7-darwin-aarch64.tar.gz\n",
+                TEST_DIGEST,
+            ),
+        );
+        assert!(generate(tmp.path(), &Options::default()).is_ok());
+    }
+
+    #[test]
+    fn rejects_mismatched_sidecar() {
+        let tmp = artifacts();
+        write(
+            tmp.path(),
+            "v0.5.7/shebe-v0.5.7-darwin-aarch64.tar.gz.sha256",
+            &"0".repeat(64),
+        );
+        let err = generate(tmp.path(), &Options::default())
+            .unwrap_err();
+        assert!(err.contains("does not match"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_missing_or_malformed_sidecar() {
+        let tmp = artifacts();
+        let sidecar = "v0.5.7/shebe-v0.5.7-darwin-aarch64.tar.gz.sha256";
+        write(tmp.path(), sidecar, "");
+        let err = generate(tmp.path(), &Options::default())
+            .unwrap_err();
+        assert!(err.contains("is empty"), "{}", err);
+
+        write(tmp.path(), sidecar, "not-a-digest");
+        let err = generate(tmp.path(), &Options::default())
+            .unwrap_err();
+        assert!(err.contains("does not contain a SHA256"), "{}", err);
+
+        fs::remove_file(tmp.path().join(sidecar)).unwrap();
+        let err = generate(tmp.path(), &Options::default())
+            .unwrap_err();
+        assert!(err.contains("failed to read"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_sidecar_for_another_archive() {
+        let tmp = artifacts();
+        write(
+            tmp.path(),
+            "v0.5.7/shebe-v0.5.7-darwin-aarch64.tar.gz.sha256",
+            &format!(
+                "{} *shebe-v0.5.7-linux-x86_64-musl.tar.gz\n",
+                TEST_DIGEST,
+            ),
+        );
+        let err = generate(tmp.path(), &Options::default())
+            .unwrap_err();
+        assert!(
+            err.contains(
+                "checksum file lists \
+                 'shebe-v0.5.7-linux-x86_64-musl.tar.gz'",
+            ),
+            "{}",
+            err,
+        );
+    }
+
+    #[test]
+    fn rejects_release_without_archives() {
+        let tmp = artifacts();
+        fs::create_dir(tmp.path().join("v0.7.0")).unwrap();
+        let err = generate(tmp.path(), &Options::default())
+            .unwrap_err();
+        assert!(err.contains("no shebe-v0.7.0"), "{}", err);
+    }
+}
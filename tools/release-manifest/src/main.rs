@@ -0,0 +1,120 @@
+//! `release-manifest <ARTIFACTS_DIR> [OPTIONS]`
+//!
+//! Writes a `releases.json` manifest for the release
+//! artifacts in `ARTIFACTS_DIR` to stdout or `--output`.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use release_manifest::{generate, Options};
+use semver::Version;
+
+const USAGE: &str = "\
+usage: release-manifest <ARTIFACTS_DIR> [OPTIONS]
+
+options:
+  --base-url <URL>                  prefix for asset URLs
+                                    (default: relative to the manifest)
+  --min-extension-version <VERSION> oldest Zed extension allowed to
+                                    install these releases
+  --output <FILE>                   write to FILE instead of stdout
+";
+
+/// What the command line asks for.
+enum Command {
+    Generate(Args),
+    Help,
+}
+
+struct Args {
+    artifacts: PathBuf,
+    output: Option<PathBuf>,
+    options: Options,
+}
+
+fn parse_args() -> Result<Command, String> {
+    let mut args = std::env::args().skip(1);
+    let mut artifacts = None;
+    let mut output = None;
+    let mut options = Options::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match arg.as_str() {
+            "--base-url" => {
+                options.base_url = Some(value(&arg)?);
+            }
+            "--min-extension-version" => {
+                let version = value(&arg)?;
+                options.min_extension_version = Some(
+                    Version::parse(version.trim_start_matches('v'))
+                        .map_err(|e| {
+                            format!(
+                                "invalid --min-extension-version \
+                                 '{}': {}",
+                                version, e,
+                            )
+                        })?,
+                );
+            }
+            "--output" => output = Some(PathBuf::from(value(&arg)?)),
+            "-h" | "--help" => return Ok(Command::Help),
+            flag if flag.starts_with('-') => {
+                return Err(format!("unknown option {}", flag));
+            }
+            _ if artifacts.is_none() => {
+                artifacts = Some(PathBuf::from(arg));
+            }
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    Ok(Command::Generate(Args {
+        artifacts: artifacts.ok_or("missing ARTIFACTS_DIR")?,
+        output,
+        options,
+    }))
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let manifest = generate(&args.artifacts, &args.options)?;
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| e.to_string())?;
+
+    match args.output {
+        Some(path) => std::fs::write(&path, json + "\n")
+            .map_err(|e| {
+                format!("failed to write {}: {}", path.display(), e)
+            }),
+        None => {
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(Command::Generate(args)) => args,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n", e);
+            eprint!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}